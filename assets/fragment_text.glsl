@@ -0,0 +1,8 @@
+#version 330 core
+in vec2 texCoord;
+uniform sampler2D glyphs;
+uniform vec4 inColor;
+out vec4 color;
+void main() {
+    color = vec4(inColor.rgb, inColor.a * texture(glyphs, texCoord).a);
+}
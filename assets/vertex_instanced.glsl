@@ -0,0 +1,10 @@
+#version 330 core
+layout (location = 0) in vec2 aPos;
+layout (location = 1) in vec2 aOffset;
+layout (location = 2) in vec4 aColor;
+uniform mat4 projection;
+out vec4 cellColor;
+void main() {
+    gl_Position = projection * vec4(aPos.x + aOffset.x, aPos.y + aOffset.y, 0.0, 1.0);
+    cellColor = aColor;
+}
@@ -0,0 +1,64 @@
+// Short sound effects. Playback is handed to rodio's mixer thread, so playing a sound never stalls
+// the game loop. Without the "audio" cargo feature every call is a no-op.
+
+#[cfg(feature = "audio")]
+mod backend {
+    use rodio::{Decoder, OutputStream, OutputStreamHandle, Source};
+    use std::io::Cursor;
+
+    const EAT_WAV: &[u8] = include_bytes!("../assets/eat.wav");
+    const DEATH_WAV: &[u8] = include_bytes!("../assets/death.wav");
+
+    pub struct Audio {
+        _stream: OutputStream, //playback stops when this is dropped
+        handle: OutputStreamHandle,
+    }
+
+    impl Audio {
+        //None if there is no usable output device, the game just runs silent then
+        pub fn open() -> Option<Audio> {
+            match OutputStream::try_default() {
+                Ok((stream, handle)) => Some(Audio { _stream: stream, handle }),
+                Err(error) => {
+                    eprintln!("Unable to open audio output, sound is off: {}", error);
+                    None
+                }
+            }
+        }
+
+        fn play(&self, wav: &'static [u8]) {
+            let played = Decoder::new(Cursor::new(wav))
+                .map_err(|error| error.to_string())
+                .and_then(|source| self.handle.play_raw(source.convert_samples()).map_err(|error| error.to_string()));
+
+            if let Err(error) = played {
+                eprintln!("Unable to play sound: {}", error);
+            }
+        }
+
+        pub fn play_eat(&self) {
+            self.play(EAT_WAV);
+        }
+
+        pub fn play_death(&self) {
+            self.play(DEATH_WAV);
+        }
+    }
+}
+
+#[cfg(not(feature = "audio"))]
+mod backend {
+    pub struct Audio;
+
+    impl Audio {
+        pub fn open() -> Option<Audio> {
+            None
+        }
+
+        pub fn play_eat(&self) {}
+
+        pub fn play_death(&self) {}
+    }
+}
+
+pub use backend::Audio;
@@ -0,0 +1,1654 @@
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt::Write;
+use std::ops::{Add, Neg, Sub};
+use std::{fmt, iter, mem};
+
+//a golden fruit is worth this much more than a normal one and only stays for a while
+pub const GOLDEN_FRUIT_GROWTH: u32 = 3;
+pub const GOLDEN_FRUIT_SCORE: usize = 5;
+pub const GOLDEN_FRUIT_TICKS: u64 = 30;
+
+//cells per side at most, which keeps cell counts like size_x * size_y far from overflowing
+pub const MAX_BOARD_SIZE: i32 = 1024;
+
+#[derive(Eq, PartialEq, Hash, Clone, Copy, Debug)]
+pub struct Point {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl Point {
+    pub const fn new(x: i32, y: i32) -> Point {
+        Point { x, y }
+    }
+
+    pub const fn origin() -> Point {
+        Point::new(0, 0)
+    }
+}
+
+impl Add for Point {
+    type Output = Point;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Point {
+            x: self.x + rhs.x,
+            y: self.y + rhs.y,
+        }
+    }
+}
+
+impl Neg for Point {
+    type Output = Point;
+
+    fn neg(self) -> Self::Output {
+        Point { x: -self.x, y: -self.y }
+    }
+}
+
+impl Sub for Point {
+    type Output = Point;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        self + (-rhs)
+    }
+}
+
+#[derive(Eq, PartialEq, Clone, Copy, Debug)]
+pub enum SnakeDirection {
+    Up,
+    Right,
+    Down,
+    Left,
+}
+
+impl SnakeDirection {
+    pub fn rotated_clockwise(self, quarter_turns: u32) -> SnakeDirection {
+        const CLOCKWISE: [SnakeDirection; 4] = [SnakeDirection::Up, SnakeDirection::Right, SnakeDirection::Down, SnakeDirection::Left];
+
+        let index = CLOCKWISE.iter().position(|direction| *direction == self).unwrap();
+        return CLOCKWISE[(index + quarter_turns as usize) % 4];
+    }
+
+    pub const fn opposite(self) -> SnakeDirection {
+        match self {
+            SnakeDirection::Up => SnakeDirection::Down,
+            SnakeDirection::Right => SnakeDirection::Left,
+            SnakeDirection::Down => SnakeDirection::Up,
+            SnakeDirection::Left => SnakeDirection::Right,
+        }
+    }
+
+    //one cell step in this direction, y grows downwards
+    pub const fn delta(self) -> Point {
+        match self {
+            SnakeDirection::Up => Point::new(0, -1),
+            SnakeDirection::Right => Point::new(1, 0),
+            SnakeDirection::Down => Point::new(0, 1),
+            SnakeDirection::Left => Point::new(-1, 0),
+        }
+    }
+}
+
+//what try_change_direction did with a requested turn
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum DirectionChange {
+    Applied,
+    Rejected,  //reversing into the own neck
+    Unchanged, //already heading that way
+}
+
+// The head and tail are only moved by the field (push_snake and friends), which keeps `occupied`
+// in sync with them. It counts parts per cell since a snake in its grace period can overlap itself.
+pub struct Snake {
+    pub head: Point,
+    pub tail: VecDeque<Point>,
+    pub direction: SnakeDirection,
+    pub pending_growth: u32, //segments still to grow, one per move
+    occupied: HashMap<Point, u32>,
+}
+
+impl Snake {
+    fn new(head: Point, direction: SnakeDirection) -> Snake {
+        let mut occupied = HashMap::new();
+        occupied.insert(head, 1);
+
+        Snake {
+            head,
+            tail: VecDeque::new(),
+            direction,
+            pending_growth: 0,
+            occupied,
+        }
+    }
+
+    //`length` parts in a straight line, the tail trailing behind the head; the cells have to be free
+    fn with_length(head: Point, direction: SnakeDirection, length: usize) -> Snake {
+        let mut snake = Snake::new(head, direction);
+        let behind = direction.opposite().delta();
+
+        let mut part = head;
+        for _ in 1..length {
+            part = part + behind;
+            snake.tail.push_front(part); //the neck stays at the back
+            *snake.occupied.entry(part).or_insert(0) += 1;
+        }
+
+        return snake;
+    }
+
+    pub fn try_change_direction(&mut self, direction: SnakeDirection) -> DirectionChange {
+        if direction == self.direction {
+            return DirectionChange::Unchanged; //holding the current direction is free
+        }
+
+        if direction == self.direction.opposite() {
+            return DirectionChange::Rejected;
+        }
+
+        self.direction = direction;
+        return DirectionChange::Applied;
+    }
+
+    //min and max corners of the box covering the head and the whole tail
+    pub fn bounding_box(&self) -> (Point, Point) {
+        let mut min = self.head;
+        let mut max = self.head;
+
+        for tail_part in &self.tail {
+            min.x = min.x.min(tail_part.x);
+            min.y = min.y.min(tail_part.y);
+            max.x = max.x.max(tail_part.x);
+            max.y = max.y.max(tail_part.y);
+        }
+
+        return (min, max);
+    }
+
+    // Same as bounding_box, but the box may cross the wrapping seam when that makes it tighter.
+    // The max corner is in unwrapped coordinates then, so it can lie past size_x/size_y.
+    pub fn wrapped_bounding_box(&self, size_x: i32, size_y: i32) -> (Point, Point) {
+        let parts = || iter::once(&self.head).chain(self.tail.iter());
+
+        let (min_x, max_x) = wrapped_span(parts().map(|part| part.x), size_x);
+        let (min_y, max_y) = wrapped_span(parts().map(|part| part.y), size_y);
+
+        return (Point::new(min_x, min_y), Point::new(max_x, max_y));
+    }
+
+    //distinct cells under the head and tail, fewer than the parts while the snake overlaps itself
+    pub fn occupied_cells(&self) -> usize {
+        self.occupied.len()
+    }
+
+    pub fn occupies(&self, point: &Point) -> bool {
+        self.occupied.contains_key(point)
+    }
+
+    //whether the body still covers point once the head moves on: the tail end leaves unless the snake grows
+    pub fn occupies_after_move(&self, point: &Point) -> bool {
+        let parts = self.occupied.get(point).copied().unwrap_or(0);
+        let vacated = self.pending_growth == 0 && self.tail.front() == Some(point);
+
+        return parts > vacated as u32;
+    }
+
+    //moves the head into new_head, the old head becomes the newest tail part
+    fn advance(&mut self, new_head: Point) {
+        let old_head = mem::replace(&mut self.head, new_head);
+        *self.occupied.entry(new_head).or_insert(0) += 1;
+
+        self.tail.push_back(old_head);
+        if !self.take_growth() {
+            self.drop_tail_end();
+        }
+    }
+
+    //false if there is no tail left to drop
+    fn drop_tail_end(&mut self) -> bool {
+        let tail_end = match self.tail.pop_front() {
+            Some(tail_end) => tail_end,
+            None => return false,
+        };
+
+        if let Entry::Occupied(mut parts) = self.occupied.entry(tail_end) {
+            *parts.get_mut() -= 1;
+            if *parts.get() == 0 {
+                parts.remove();
+            }
+        }
+
+        return true;
+    }
+
+    //whether this move grows the snake, using up one pending segment if so
+    #[inline]
+    fn take_growth(&mut self) -> bool {
+        if self.pending_growth == 0 {
+            return false;
+        }
+
+        self.pending_growth -= 1;
+        return true;
+    }
+}
+
+//shortest span on a wrapping axis that covers all coordinates: it starts right after the largest free gap
+pub fn wrapped_span(coordinates: impl Iterator<Item = i32>, size: i32) -> (i32, i32) {
+    let mut occupied = coordinates.collect::<Vec<i32>>();
+    occupied.sort_unstable();
+    occupied.dedup();
+
+    let mut largest_gap = 0;
+    let mut start = occupied[0];
+
+    for (i, coordinate) in occupied.iter().enumerate() {
+        let next = occupied.get(i + 1).copied().unwrap_or(occupied[0] + size);
+        let gap = next - coordinate - 1;
+
+        if gap > largest_gap {
+            largest_gap = gap;
+            start = next % size;
+        }
+    }
+
+    return (start, start + size - largest_gap - 1);
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum FruitKind {
+    Normal,
+    Golden, //grows GOLDEN_FRUIT_GROWTH segments and scores GOLDEN_FRUIT_SCORE, reverts to normal after GOLDEN_FRUIT_TICKS moves
+}
+
+pub struct Fruit(pub Point, pub FruitKind);
+
+// Picks where the next fruit spawns; it must never be under the head, any tail part or a blocked cell.
+// None when no such cell is left, i.e. the snake fills the board.
+pub trait FruitPlacer {
+    fn place(&mut self, snake: &Snake, blocked: &HashSet<Point>, size_x: i32, size_y: i32, rng: &mut StdRng) -> Option<Fruit>;
+}
+
+//uniform over the free cells, which are collected first so a nearly full board costs no retries
+pub struct RandomFruitPlacer;
+
+impl FruitPlacer for RandomFruitPlacer {
+    fn place(&mut self, snake: &Snake, blocked: &HashSet<Point>, size_x: i32, size_y: i32, rng: &mut StdRng) -> Option<Fruit> {
+        let free_cells: Vec<Point> = (0..size_y)
+            .flat_map(|y| (0..size_x).map(move |x| Point::new(x, y)))
+            .filter(|cell| !snake.occupies(cell) && !blocked.contains(cell))
+            .collect();
+
+        return free_cells.choose(rng).map(|cell| Fruit(*cell, FruitKind::Normal));
+    }
+}
+
+// Walks a seeded shuffle of all cells, skipping occupied ones, and reshuffles once it runs out.
+// Every cell gets its turn over a long game and fruit never clusters.
+#[derive(Default)]
+pub struct ShuffledFruitPlacer {
+    order: Vec<Point>,
+    next: usize,
+}
+
+impl ShuffledFruitPlacer {
+    pub fn new() -> ShuffledFruitPlacer {
+        ShuffledFruitPlacer { order: Vec::new(), next: 0 }
+    }
+}
+
+impl FruitPlacer for ShuffledFruitPlacer {
+    fn place(&mut self, snake: &Snake, blocked: &HashSet<Point>, size_x: i32, size_y: i32, rng: &mut StdRng) -> Option<Fruit> {
+        //the rest of the current shuffle plus one whole fresh one visits every cell at least once
+        for _ in 0..2 * size_x * size_y {
+            if self.next == self.order.len() {
+                self.order = (0..size_y).flat_map(|y| (0..size_x).map(move |x| Point::new(x, y))).collect();
+                self.order.shuffle(rng);
+                self.next = 0;
+            }
+
+            let cell = &self.order[self.next];
+            self.next += 1;
+
+            if !snake.occupies(cell) && !blocked.contains(cell) {
+                return Some(Fruit(*cell, FruitKind::Normal));
+            }
+        }
+
+        return None;
+    }
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum DeathCause {
+    SelfCollision,
+    Wall, //only without wrapping
+    Obstacle,
+}
+
+//what a single SnakeGameField::step did
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum StepOutcome {
+    Continued { ate: bool }, //ate: the head reached the fruit, the snake grows over the next moves
+    Died,
+    Won,
+}
+
+// Decides whether moving the head into next_head kills the snake. The field consults it before
+// every move, so rule variants (phasing through the own tail once, ...) don't need a new push_snake.
+pub trait CollisionRule {
+    fn is_collision(&self, field: &SnakeGameField, next_head: &Point) -> Option<DeathCause>;
+}
+
+//running into a wall, an obstacle or any part of the body that is still there after the move
+pub struct StandardCollisionRule;
+
+impl CollisionRule for StandardCollisionRule {
+    fn is_collision(&self, field: &SnakeGameField, next_head: &Point) -> Option<DeathCause> {
+        if !field.in_bounds(next_head) {
+            return Some(DeathCause::Wall);
+        }
+
+        if field.obstacles.contains(next_head) {
+            return Some(DeathCause::Obstacle);
+        }
+
+        if field.snake.occupies_after_move(next_head) {
+            return Some(DeathCause::SelfCollision);
+        }
+
+        return None;
+    }
+}
+
+#[derive(Debug)]
+pub enum BoardError {
+    Empty,
+    RaggedRow { row: usize, expected: usize, found: usize },
+    UnknownCell(char),
+    MissingSnake,
+    MultipleSnakes,
+    MultipleFruits,
+    TooLarge { width: usize, height: usize },
+}
+
+impl fmt::Display for BoardError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BoardError::Empty => write!(f, "the layout has no rows"),
+            BoardError::RaggedRow { row, expected, found } => write!(f, "row {} has {} cells, expected {}", row + 1, found, expected),
+            BoardError::UnknownCell(cell) => write!(f, "unknown cell '{}'", cell),
+            BoardError::MissingSnake => write!(f, "no snake start ('S')"),
+            BoardError::MultipleSnakes => write!(f, "more than one snake start ('S')"),
+            BoardError::MultipleFruits => write!(f, "more than one fruit ('*' or 'F')"),
+            BoardError::TooLarge { width, height } => write!(f, "a {}x{} board is larger than {} cells per side", width, height, MAX_BOARD_SIZE),
+        }
+    }
+}
+
+// What a board looks like before the first move. The rng does the rest, so a layout and a seed
+// are enough to set up the same round again.
+#[derive(Clone, PartialEq, Debug)]
+pub struct BoardLayout {
+    pub size_x: i32,
+    pub size_y: i32,
+    pub spawn: Point,
+    pub obstacles: HashSet<Point>,
+    pub fruit: Option<Point>, //the first fruit, placed like any other when None
+}
+
+impl BoardLayout {
+    //no obstacles, the snake starts in the center
+    pub fn empty(size_x: i32, size_y: i32) -> BoardLayout {
+        BoardLayout {
+            size_x,
+            size_y,
+            spawn: Point::new(size_x / 2, size_y / 2),
+            obstacles: HashSet::new(),
+            fruit: None,
+        }
+    }
+
+    // Parses a board drawn as text, one row per line: '.' empty, 'S' snake start, '*' (or 'F') fruit
+    // and '#' obstacle. The size comes from the grid; trailing whitespace and blank lines at the end are
+    // ignored.
+    pub fn parse(layout: &str) -> Result<BoardLayout, BoardError> {
+        let mut rows: Vec<&str> = layout.lines().map(str::trim_end).collect();
+        while rows.last() == Some(&"") {
+            rows.pop();
+        }
+
+        let width = rows.first().ok_or(BoardError::Empty)?.chars().count();
+        if width == 0 {
+            return Err(BoardError::Empty);
+        }
+
+        let height = rows.len();
+        if width > MAX_BOARD_SIZE as usize || height > MAX_BOARD_SIZE as usize {
+            return Err(BoardError::TooLarge { width, height });
+        }
+
+        let mut snake_start = None;
+        let mut fruit = None;
+        let mut obstacles = HashSet::new();
+
+        for (y, row) in rows.iter().enumerate() {
+            let found = row.chars().count();
+            if found != width {
+                return Err(BoardError::RaggedRow {
+                    row: y,
+                    expected: width,
+                    found,
+                });
+            }
+
+            for (x, cell) in row.chars().enumerate() {
+                let point = Point::new(x as i32, y as i32);
+
+                match cell {
+                    '.' => {}
+                    'S' if snake_start.is_some() => return Err(BoardError::MultipleSnakes),
+                    'S' => snake_start = Some(point),
+                    '*' | 'F' if fruit.is_some() => return Err(BoardError::MultipleFruits),
+                    '*' | 'F' => fruit = Some(point),
+                    '#' => {
+                        obstacles.insert(point);
+                    }
+                    _ => return Err(BoardError::UnknownCell(cell)),
+                }
+            }
+        }
+
+        return Ok(BoardLayout {
+            size_x: width as i32,
+            size_y: height as i32,
+            spawn: snake_start.ok_or(BoardError::MissingSnake)?,
+            obstacles,
+            fruit,
+        });
+    }
+}
+
+pub struct SnakeGameField {
+    pub size_x: i32,
+    pub size_y: i32,
+    spawn: Point,        //where the snake starts, the board center unless a layout says otherwise
+    start_length: usize, //parts at the start of a round, see set_start_length
+    pub wrap_x: bool,    //leaving the board sideways re-enters on the other side instead of hitting a wall
+    pub wrap_y: bool,    //same for the top and bottom edges
+    pub snake: Snake,
+    pub fruit: Option<Fruit>,      //None only while the snake fills every free cell
+    pub golden_chance: f64,        //chance that eating a normal fruit spawns a golden one next
+    pub practice: bool,            //eating scores but never grows the snake, so there is no winning either
+    golden_until: u64,             //tick at which a golden fruit turns normal again
+    pub obstacles: HashSet<Point>, //static cells that kill the snake and never get a fruit
+    fruit_placer: Box<dyn FruitPlacer>,
+    pub collision_rule: Box<dyn CollisionRule>,
+    rng: StdRng,
+    pub ticks: u64, //moves made so far
+    score: usize,   //fruits eaten, unlike the tail length it isn't reduced by shrinking
+    pub grace_ticks: u64,
+    pub subcell_steps: u32, //ticks per cell step, 1 moves a whole cell every tick
+    subcell_progress: u32,
+    pub idle_shrink: Option<IdleShrink>,
+    idle_ticks: u64, //moves since the last fruit
+    pub wrap_cooldown: u64,
+    last_wrap_tick: Option<u64>,
+}
+
+//starvation mode: going too long without eating costs tail segments
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct IdleShrink {
+    pub ticks: u64,  //moves without eating per lost segment
+    pub fatal: bool, //whether starving with no tail left ends the game, otherwise the bare head is the floor
+}
+
+//why SnakeGameField::set_start_length refused a length
+#[derive(Debug)]
+pub enum StartLengthError {
+    Zero,
+    OffBoard { length: usize, room: usize }, //the tail would stick out past the edge behind the spawn
+    Blocked(Point),                          //an obstacle is in the way
+}
+
+impl fmt::Display for StartLengthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StartLengthError::Zero => write!(f, "the snake needs at least its head"),
+            StartLengthError::OffBoard { length, room } => write!(f, "a snake of length {} doesn't fit, there is room for {}", length, room),
+            StartLengthError::Blocked(point) => write!(f, "the starting tail runs into the obstacle at ({}, {})", point.x, point.y),
+        }
+    }
+}
+
+impl std::error::Error for StartLengthError {}
+
+impl SnakeGameField {
+    pub fn create(size_x: i32, size_y: i32, seed: u64, fruit_placer: Box<dyn FruitPlacer>) -> SnakeGameField {
+        SnakeGameField::from_layout(&BoardLayout::empty(size_x, size_y), seed, fruit_placer)
+    }
+
+    //a board drawn as text, see BoardLayout::parse
+    pub fn from_ascii(layout: &str, seed: u64, fruit_placer: Box<dyn FruitPlacer>) -> Result<SnakeGameField, BoardError> {
+        Ok(SnakeGameField::from_layout(&BoardLayout::parse(layout)?, seed, fruit_placer))
+    }
+
+    pub fn from_layout(layout: &BoardLayout, seed: u64, fruit_placer: Box<dyn FruitPlacer>) -> SnakeGameField {
+        let mut field = SnakeGameField {
+            size_x: layout.size_x,
+            size_y: layout.size_y,
+            spawn: layout.spawn,
+            start_length: 1,
+            wrap_x: true,
+            wrap_y: true,
+            snake: Snake::new(layout.spawn, SnakeDirection::Right),
+            fruit: layout.fruit.map(|fruit| Fruit(fruit, FruitKind::Normal)),
+            golden_chance: 0.0,
+            practice: false,
+            golden_until: 0,
+            obstacles: layout.obstacles.clone(),
+            fruit_placer,
+            collision_rule: Box::new(StandardCollisionRule),
+            rng: StdRng::seed_from_u64(seed),
+            ticks: 0,
+            score: 0,
+            grace_ticks: 0,
+            subcell_steps: 1,
+            subcell_progress: 0,
+            idle_shrink: None,
+            idle_ticks: 0,
+            wrap_cooldown: 0,
+            last_wrap_tick: None,
+        };
+
+        if field.fruit.is_none() {
+            field.fruit = field.spawn_fruit();
+        }
+
+        return field;
+    }
+
+    // The inverse of from_ascii, with 'o' for tail segments. Those can't be loaded back, so only a
+    // snake that hasn't grown yet round-trips.
+    pub fn to_ascii(&self) -> String {
+        let mut ascii = String::with_capacity(((self.size_x + 1) * self.size_y) as usize);
+
+        for y in 0..self.size_y {
+            for x in 0..self.size_x {
+                let point = Point::new(x, y);
+
+                ascii.push(if point == self.snake.head {
+                    'S'
+                } else if self.snake.tail.contains(&point) {
+                    'o'
+                } else if self.fruit.as_ref().map(|fruit| &fruit.0) == Some(&point) {
+                    '*'
+                } else if self.obstacles.contains(&point) {
+                    '#'
+                } else {
+                    '.'
+                });
+            }
+
+            ascii.push('\n');
+        }
+
+        return ascii;
+    }
+
+    // Makes every round start with `length` parts (the head is one), the tail laid out straight
+    // to the left of the spawn. It has to fit on the board without wrapping and miss the obstacles,
+    // so set the board up first. Takes effect right away; a fruit under the new tail moves.
+    pub fn set_start_length(&mut self, length: usize) -> Result<(), StartLengthError> {
+        if length == 0 {
+            return Err(StartLengthError::Zero);
+        }
+
+        let behind = SnakeDirection::Right.opposite().delta();
+        let mut part = self.spawn;
+        for placed in 1..length {
+            part = part + behind;
+
+            if !self.in_bounds(&part) {
+                return Err(StartLengthError::OffBoard { length, room: placed });
+            }
+            if self.obstacles.contains(&part) {
+                return Err(StartLengthError::Blocked(part));
+            }
+        }
+
+        self.start_length = length;
+        self.snake = Snake::with_length(self.spawn, SnakeDirection::Right, length);
+
+        if matches!(&self.fruit, Some(fruit) if self.snake.occupies(&fruit.0)) {
+            self.fruit = self.spawn_fruit();
+        }
+
+        return Ok(());
+    }
+
+    // Starts a new round on the same board: the snake back at the spawn heading right, as long as
+    // set_start_length says, a fresh fruit and all counters cleared. Settings like the grace period
+    // or collision rule are kept.
+    pub fn reset(&mut self) {
+        self.snake = Snake::with_length(self.spawn, SnakeDirection::Right, self.start_length);
+
+        self.ticks = 0;
+        self.score = 0;
+        self.subcell_progress = 0;
+        self.idle_ticks = 0;
+        self.last_wrap_tick = None;
+
+        self.fruit = self.spawn_fruit();
+    }
+
+    // Only the head eats: a fruit under a tail part is left alone. Spawning never puts one there,
+    // so it can only happen if the field is edited directly.
+    pub fn handle_snake_fruit_collision(&mut self) {
+        let eaten = match &self.fruit {
+            Some(fruit) if fruit.0 == self.snake.head => fruit.1,
+            _ => return,
+        };
+        let (growth, points) = match eaten {
+            FruitKind::Normal => (1, 1),
+            FruitKind::Golden => (GOLDEN_FRUIT_GROWTH, GOLDEN_FRUIT_SCORE),
+        };
+        if !self.practice {
+            self.snake.pending_growth += growth;
+        }
+        self.score += points;
+        self.idle_ticks = 0;
+
+        self.fruit = self.spawn_fruit();
+
+        //golden fruit never follows golden fruit; the roll is skipped entirely when disabled so seeds stay stable
+        if let Some(fruit) = &mut self.fruit {
+            if eaten == FruitKind::Normal && self.golden_chance > 0.0 && self.rng.gen_bool(self.golden_chance) {
+                fruit.1 = FruitKind::Golden;
+                self.golden_until = self.ticks + GOLDEN_FRUIT_TICKS;
+            }
+        }
+    }
+
+    // Accumulates one sub-cell tick of movement. Collisions still work on whole cells, so the snake
+    // only moves (and the rest of the tick runs) when this crosses into the next cell.
+    fn advance_subcell(&mut self) -> bool {
+        self.subcell_progress += 1;
+
+        if self.subcell_progress < self.subcell_steps {
+            return false;
+        }
+
+        self.subcell_progress = 0;
+        return true;
+    }
+
+    //sub-cell ticks done towards the next cell step, 0 right after a step
+    pub fn subcell_progress(&self) -> u32 {
+        self.subcell_progress
+    }
+
+    //whether the next step moves the snake into the next cell, rather than only a sub-cell tick
+    pub fn cell_step_due(&self) -> bool {
+        self.subcell_progress + 1 >= self.subcell_steps
+    }
+
+    // Besides obstacles, the cell the head enters next is blocked: a fruit there would be eaten on the
+    // very next move. It's only allowed when it is the last free cell left.
+    fn spawn_fruit(&mut self) -> Option<Fruit> {
+        let mut blocked = self.obstacles.clone();
+
+        let next_head = self.next_head();
+        if self.free_cells() > 1 && self.in_bounds(&next_head) {
+            blocked.insert(next_head);
+        }
+
+        self.fruit_placer.place(&self.snake, &blocked, self.size_x, self.size_y, &mut self.rng)
+    }
+
+    //the cell the head moves into on the next push_snake, off the board if it runs into a wall
+    pub fn next_head(&self) -> Point {
+        self.head_after_move(self.snake.direction)
+    }
+
+    //where the head would end up moving one cell towards `direction`
+    pub fn head_after_move(&self, direction: SnakeDirection) -> Point {
+        self.move_head(direction).0
+    }
+
+    //head_after_move, plus whether the move wraps around the board
+    fn move_head(&self, direction: SnakeDirection) -> (Point, bool) {
+        let moved = self.snake.head + direction.delta();
+        let mut head = moved;
+
+        //an axis without wrapping leaves the head off the board for the collision check to catch
+        if self.wrap_x {
+            head.x = head.x.rem_euclid(self.size_x);
+        }
+        if self.wrap_y {
+            head.y = head.y.rem_euclid(self.size_y);
+        }
+
+        return (head, head != moved);
+    }
+
+    pub fn in_bounds(&self, point: &Point) -> bool {
+        point.x >= 0 && point.x < self.size_x && point.y >= 0 && point.y < self.size_y
+    }
+
+    pub fn push_snake(&mut self) {
+        self.ticks += 1;
+
+        let (new_head, wrapped) = self.move_head(self.snake.direction);
+
+        self.snake.advance(new_head);
+
+        if wrapped {
+            self.handle_wrap();
+        }
+
+        //an uneaten golden fruit stays where it is as a normal one
+        if let Some(fruit) = &mut self.fruit {
+            if fruit.1 == FruitKind::Golden && self.ticks >= self.golden_until {
+                fruit.1 = FruitKind::Normal;
+            }
+        }
+    }
+
+    //whether the head went across the edge of the board on the latest move
+    pub fn wrapped_last_move(&self) -> bool {
+        self.last_wrap_tick == Some(self.ticks)
+    }
+
+    // Wrapping again within wrap_cooldown moves of the previous wrap costs a tail segment, so
+    // hugging an edge to stall doesn't pay off. A cooldown of 0 never penalizes.
+    fn handle_wrap(&mut self) {
+        if let Some(last_wrap_tick) = self.last_wrap_tick {
+            if self.ticks - last_wrap_tick <= self.wrap_cooldown {
+                self.snake.drop_tail_end();
+            }
+        }
+
+        self.last_wrap_tick = Some(self.ticks);
+    }
+
+    // Called after every move. Drops the tail end once the snake has gone idle_shrink.ticks moves
+    // without eating. Returns true if the snake starved: a segment was due, none was left and
+    // starving is fatal.
+    pub fn shrink_if_idle(&mut self) -> bool {
+        let idle_shrink = match self.idle_shrink {
+            Some(idle_shrink) => idle_shrink,
+            None => return false,
+        };
+
+        self.idle_ticks += 1;
+        if self.idle_ticks < idle_shrink.ticks {
+            return false;
+        }
+
+        self.idle_ticks = 0;
+        if self.snake.drop_tail_end() {
+            return false;
+        }
+
+        return idle_shrink.fatal;
+    }
+
+    // Whether the upcoming move kills the snake, checked before push_snake. During the grace period
+    // the snake passes through itself and obstacles, but a wall is still fatal: the head would leave
+    // the board otherwise.
+    pub fn check_snake_collision(&self) -> bool {
+        match self.would_collide(&self.next_head()) {
+            Some(DeathCause::Wall) => true,
+            Some(DeathCause::SelfCollision) | Some(DeathCause::Obstacle) => !self.in_grace_period(),
+            None => false,
+        }
+    }
+
+    //what moving the head into next_head would die of, without moving it
+    pub fn would_collide(&self, next_head: &Point) -> Option<DeathCause> {
+        self.collision_rule.is_collision(self, next_head)
+    }
+
+    //during the first grace_ticks moves only walls can kill the snake
+    pub fn in_grace_period(&self) -> bool {
+        self.ticks < self.grace_ticks
+    }
+
+    pub fn score(&self) -> usize {
+        self.score
+    }
+
+    //every cell that isn't an obstacle is covered by the snake
+    pub fn check_win(&self) -> bool {
+        self.free_cells() == 0
+    }
+
+    // Cells with neither an obstacle nor a snake part on them. During the grace period parts can
+    // overlap each other and sit on obstacles, so this counts cells, not parts.
+    fn free_cells(&self) -> usize {
+        let snake_cells = self.snake.occupied.keys().filter(|cell| !self.obstacles.contains(cell)).count();
+        return (self.size_x * self.size_y) as usize - self.obstacles.len() - snake_cells;
+    }
+
+    // One whole tick of the game: turns towards `input` if that's a legal turn, eats a fruit under
+    // the head, then moves unless the move is fatal. Once it returns Died or Won the round is over
+    // and the field should be reset before stepping again. With sub-cells only every subcell_steps-th
+    // tick is a cell step, the others leave the snake alone and ignore `input`.
+    pub fn step(&mut self, input: Option<SnakeDirection>) -> StepOutcome {
+        if !self.advance_subcell() {
+            return StepOutcome::Continued { ate: false };
+        }
+
+        if let Some(direction) = input {
+            self.snake.try_change_direction(direction);
+        }
+
+        let score = self.score;
+        self.handle_snake_fruit_collision();
+        if self.check_snake_collision() {
+            return StepOutcome::Died;
+        }
+
+        self.push_snake();
+        if self.shrink_if_idle() {
+            return StepOutcome::Died;
+        }
+
+        if !self.practice && self.check_win() {
+            return StepOutcome::Won;
+        }
+
+        return StepOutcome::Continued { ate: self.score > score };
+    }
+
+    // Multi-line snapshot of the game state for bug reports and desync hunting. Tail segments are
+    // listed from the tail end towards the head, which is their order in the deque.
+    pub fn debug_dump(&self) -> String {
+        let mut dump = String::new();
+
+        //writing into a String can't fail
+        writeln!(dump, "tick: {}", self.ticks).unwrap();
+        writeln!(dump, "direction: {:?}", self.snake.direction).unwrap();
+        writeln!(dump, "head: ({}, {})", self.snake.head.x, self.snake.head.y).unwrap();
+        for (i, part) in self.snake.tail.iter().enumerate() {
+            writeln!(dump, "tail[{}]: ({}, {})", i, part.x, part.y).unwrap();
+        }
+        writeln!(dump, "pending growth: {}", self.snake.pending_growth).unwrap();
+        match &self.fruit {
+            Some(fruit) => writeln!(dump, "fruit: ({}, {}) {:?}", fruit.0.x, fruit.0.y, fruit.1).unwrap(),
+            None => writeln!(dump, "fruit: none").unwrap(),
+        }
+        writeln!(dump, "occupied cells: {}", self.snake.occupied_cells()).unwrap();
+
+        return dump;
+    }
+}
+
+// Greedy attract-mode driver: heads for the fruit along the axis with the larger distance first
+// (the short way around on a wrapping board), skipping any move that would be fatal right away.
+// Not optimal, it can still coil itself into a dead end.
+pub fn autopilot_direction(field: &SnakeGameField) -> SnakeDirection {
+    let head = &field.snake.head;
+
+    let shortest_delta = |delta: i32, size: i32, wrap: bool| {
+        if wrap && delta.abs() * 2 > size {
+            delta - delta.signum() * size
+        } else {
+            delta
+        }
+    };
+    let (dx, dy) = match &field.fruit {
+        Some(Fruit(fruit, _)) => (
+            shortest_delta(fruit.x - head.x, field.size_x, field.wrap_x),
+            shortest_delta(fruit.y - head.y, field.size_y, field.wrap_y),
+        ),
+        None => (0, 0), //nothing to chase, only stay alive
+    };
+
+    let horizontal = (dx, if dx < 0 { SnakeDirection::Left } else { SnakeDirection::Right });
+    let vertical = (dy, if dy < 0 { SnakeDirection::Up } else { SnakeDirection::Down });
+    let axes = if dx.abs() >= dy.abs() {
+        [horizontal, vertical]
+    } else {
+        [vertical, horizontal]
+    };
+    let towards_fruit = axes.iter().filter(|(delta, _)| *delta != 0).map(|(_, direction)| *direction);
+
+    //when no move towards the fruit is safe, anything that keeps the snake alive, straight on first
+    let current = field.snake.direction;
+    let fallback = [current, current.rotated_clockwise(1), current.rotated_clockwise(3)];
+
+    let reverse = current.opposite();
+    return towards_fruit
+        .chain(fallback.iter().copied())
+        .filter(|direction| *direction != reverse)
+        .find(|direction| field.would_collide(&field.head_after_move(*direction)).is_none())
+        .unwrap_or(current);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //an empty board of the given size with no fruit on it, the snake a bare head at `head`
+    fn field_with_head(size_x: i32, size_y: i32, head: Point, direction: SnakeDirection) -> SnakeGameField {
+        let mut field = SnakeGameField::create(size_x, size_y, 1, Box::new(RandomFruitPlacer));
+        field.snake = Snake::new(head, direction);
+        field.fruit = None;
+        return field;
+    }
+
+    //`parts` run from the head to the tail end
+    fn snake_from_parts(parts: &[Point], direction: SnakeDirection) -> Snake {
+        let mut snake = Snake::new(parts[0], direction);
+        for part in &parts[1..] {
+            snake.tail.push_front(*part);
+            *snake.occupied.entry(*part).or_insert(0) += 1;
+        }
+
+        return snake;
+    }
+
+    #[test]
+    fn push_snake_moves_one_cell_towards_the_direction() {
+        let cases = [
+            (SnakeDirection::Up, Point::new(5, 4)),
+            (SnakeDirection::Down, Point::new(5, 6)),
+            (SnakeDirection::Left, Point::new(4, 5)),
+            (SnakeDirection::Right, Point::new(6, 5)),
+        ];
+
+        for (direction, expected) in cases {
+            let mut field = field_with_head(10, 10, Point::new(5, 5), direction);
+            field.push_snake();
+            assert_eq!(field.snake.head, expected, "moving {:?}", direction);
+            assert!(field.snake.tail.is_empty());
+        }
+    }
+
+    #[test]
+    fn push_snake_wraps_at_every_edge() {
+        let cases = [
+            (Point::new(9, 5), SnakeDirection::Right, Point::new(0, 5)),
+            (Point::new(0, 5), SnakeDirection::Left, Point::new(9, 5)),
+            (Point::new(5, 0), SnakeDirection::Up, Point::new(5, 9)),
+            (Point::new(5, 9), SnakeDirection::Down, Point::new(5, 0)),
+        ];
+
+        for (head, direction, expected) in cases {
+            let mut field = field_with_head(10, 10, head, direction);
+            field.push_snake();
+            assert_eq!(field.snake.head, expected, "moving {:?} from {:?}", direction, head);
+        }
+    }
+
+    #[test]
+    fn snake_grows_on_the_move_after_eating() {
+        let mut field = field_with_head(10, 10, Point::new(5, 5), SnakeDirection::Right);
+        field.fruit = Some(Fruit(Point::new(5, 5), FruitKind::Normal));
+
+        field.handle_snake_fruit_collision();
+        assert_eq!(field.snake.pending_growth, 1);
+
+        field.push_snake();
+        assert_eq!(field.snake.head, Point::new(6, 5));
+        assert_eq!(field.snake.tail, [Point::new(5, 5)]);
+
+        //growth is used up, the next move keeps the length
+        field.push_snake();
+        assert_eq!(field.snake.tail, [Point::new(6, 5)]);
+    }
+
+    #[test]
+    fn moving_into_a_middle_segment_is_fatal() {
+        let mut field = field_with_head(10, 10, Point::new(2, 2), SnakeDirection::Down);
+        let parts = [
+            Point::new(2, 2),
+            Point::new(3, 2),
+            Point::new(3, 3),
+            Point::new(2, 3),
+            Point::new(1, 3),
+            Point::new(1, 4),
+        ];
+        field.snake = snake_from_parts(&parts, SnakeDirection::Down);
+
+        assert!(field.snake.occupies_after_move(&Point::new(2, 3)));
+        assert_eq!(StandardCollisionRule.is_collision(&field, &Point::new(2, 3)), Some(DeathCause::SelfCollision));
+        assert_eq!(field.step(None), StepOutcome::Died);
+    }
+
+    #[test]
+    fn moving_into_the_leaving_tail_end_survives() {
+        let mut field = field_with_head(10, 10, Point::new(2, 2), SnakeDirection::Down);
+        let parts = [Point::new(2, 2), Point::new(3, 2), Point::new(3, 3), Point::new(2, 3)];
+        field.snake = snake_from_parts(&parts, SnakeDirection::Down);
+
+        assert!(!field.snake.occupies_after_move(&Point::new(2, 3)));
+        assert_eq!(StandardCollisionRule.is_collision(&field, &Point::new(2, 3)), None);
+        assert_eq!(field.step(None), StepOutcome::Continued { ate: false });
+        assert_eq!(field.snake.head, Point::new(2, 3));
+
+        //a growing snake keeps its tail end in place, so the same move is fatal
+        field.snake = snake_from_parts(&parts, SnakeDirection::Down);
+        field.snake.pending_growth = 1;
+        assert!(field.snake.occupies_after_move(&Point::new(2, 3)));
+        assert_eq!(field.step(None), StepOutcome::Died);
+    }
+
+    #[test]
+    fn reversing_into_the_neck_is_ignored() {
+        let mut field = field_with_head(10, 10, Point::new(5, 5), SnakeDirection::Right);
+        field.snake = snake_from_parts(&[Point::new(5, 5), Point::new(4, 5)], SnakeDirection::Right);
+
+        assert_eq!(field.step(Some(SnakeDirection::Left)), StepOutcome::Continued { ate: false });
+        assert_eq!(field.snake.direction, SnakeDirection::Right);
+        assert_eq!(field.snake.head, Point::new(6, 5));
+        assert_eq!(field.snake.tail, [Point::new(5, 5)]);
+    }
+
+    #[test]
+    fn grace_period_ignores_self_and_obstacles_but_not_walls() {
+        let mut field = field_with_head(10, 10, Point::new(5, 5), SnakeDirection::Right);
+        field.grace_ticks = 2;
+        field.obstacles.insert(Point::new(6, 5));
+        field.obstacles.insert(Point::new(8, 5));
+
+        //through the first obstacle within the grace period, into the second one after it
+        assert_eq!(field.step(None), StepOutcome::Continued { ate: false });
+        assert_eq!(field.step(None), StepOutcome::Continued { ate: false });
+        assert!(!field.in_grace_period());
+        assert_eq!(field.step(None), StepOutcome::Died);
+
+        let mut field = field_with_head(10, 10, Point::new(9, 5), SnakeDirection::Right);
+        field.wrap_x = false;
+        field.grace_ticks = 10;
+        assert!(field.in_grace_period());
+        assert_eq!(field.step(None), StepOutcome::Died);
+    }
+
+    #[test]
+    fn wrapping_is_detected_on_a_two_cell_axis() {
+        let mut field = field_with_head(2, 5, Point::new(0, 2), SnakeDirection::Right);
+
+        field.push_snake();
+        assert_eq!(field.snake.head, Point::new(1, 2));
+        assert!(!field.wrapped_last_move());
+
+        field.push_snake();
+        assert_eq!(field.snake.head, Point::new(0, 2));
+        assert!(field.wrapped_last_move());
+    }
+
+    #[test]
+    fn two_subcells_advance_one_cell_every_two_ticks() {
+        let mut field = field_with_head(10, 10, Point::new(5, 5), SnakeDirection::Right);
+        field.subcell_steps = 2;
+
+        for cell in 1..=3 {
+            assert!(!field.cell_step_due());
+            assert_eq!(field.step(None), StepOutcome::Continued { ate: false });
+            assert_eq!(field.snake.head, Point::new(4 + cell, 5));
+
+            assert!(field.cell_step_due());
+            assert_eq!(field.step(None), StepOutcome::Continued { ate: false });
+            assert_eq!(field.snake.head, Point::new(5 + cell, 5));
+            assert_eq!(field.ticks, cell as u64);
+        }
+    }
+
+    #[test]
+    fn debug_dump_lists_the_field_state() {
+        let mut field = field_with_head(10, 10, Point::new(5, 5), SnakeDirection::Right);
+        field.snake = snake_from_parts(&[Point::new(5, 5), Point::new(4, 5), Point::new(4, 5)], SnakeDirection::Right);
+        field.snake.pending_growth = 2;
+        field.fruit = Some(Fruit(Point::new(1, 8), FruitKind::Golden));
+        field.ticks = 12;
+
+        let expected = "\
+tick: 12
+direction: Right
+head: (5, 5)
+tail[0]: (4, 5)
+tail[1]: (4, 5)
+pending growth: 2
+fruit: (1, 8) Golden
+occupied cells: 2
+";
+        assert_eq!(field.debug_dump(), expected);
+    }
+
+    #[test]
+    fn from_ascii_loads_a_layout() {
+        let layout = "\
+#....
+..S..
+....*
+";
+        let field = SnakeGameField::from_ascii(layout, 1, Box::new(RandomFruitPlacer)).unwrap();
+
+        assert_eq!((field.size_x, field.size_y), (5, 3));
+        assert_eq!(field.snake.head, Point::new(2, 1));
+        assert!(field.snake.tail.is_empty());
+        assert_eq!(field.obstacles, [Point::new(0, 0)].iter().copied().collect());
+        assert_eq!(field.fruit.map(|fruit| (fruit.0, fruit.1)), Some((Point::new(4, 2), FruitKind::Normal)));
+    }
+
+    #[test]
+    fn from_ascii_rejects_ragged_rows() {
+        let error = SnakeGameField::from_ascii("...\n.S\n...\n", 1, Box::new(RandomFruitPlacer)).err();
+        assert!(matches!(error, Some(BoardError::RaggedRow { row: 1, expected: 3, found: 2 })));
+    }
+
+    #[test]
+    fn to_ascii_round_trips_a_layout() {
+        let layout = "\
+..#..
+.S...
+#...*
+";
+        let field = SnakeGameField::from_ascii(layout, 1, Box::new(RandomFruitPlacer)).unwrap();
+        assert_eq!(field.to_ascii(), layout);
+    }
+
+    #[test]
+    fn layout_parse_accepts_a_valid_layout() {
+        //'F' is a fruit too, trailing spaces and blank lines don't count
+        let layout = BoardLayout::parse("S..  \n.#F\n\n\n").unwrap();
+
+        assert_eq!((layout.size_x, layout.size_y), (3, 2));
+        assert_eq!(layout.spawn, Point::new(0, 0));
+        assert_eq!(layout.obstacles, [Point::new(1, 1)].iter().copied().collect());
+        assert_eq!(layout.fruit, Some(Point::new(2, 1)));
+    }
+
+    #[test]
+    fn layout_parse_rejects_invalid_layouts() {
+        assert!(matches!(BoardLayout::parse(""), Err(BoardError::Empty)));
+        assert!(matches!(BoardLayout::parse("\n\n"), Err(BoardError::Empty)));
+        assert!(matches!(BoardLayout::parse("..\n.."), Err(BoardError::MissingSnake)));
+        assert!(matches!(BoardLayout::parse("S.\n.S"), Err(BoardError::MultipleSnakes)));
+        assert!(matches!(BoardLayout::parse("S*\n.F"), Err(BoardError::MultipleFruits)));
+        assert!(matches!(BoardLayout::parse("S.\n.x"), Err(BoardError::UnknownCell('x'))));
+        assert!(matches!(BoardLayout::parse("S..\n.."), Err(BoardError::RaggedRow { .. })));
+        assert!(matches!(
+            BoardLayout::parse(&"S".repeat(2000)),
+            Err(BoardError::TooLarge { width: 2000, height: 1 })
+        ));
+    }
+
+    #[test]
+    fn step_reports_each_outcome() {
+        let mut field = field_with_head(10, 10, Point::new(5, 5), SnakeDirection::Right);
+        assert_eq!(field.step(None), StepOutcome::Continued { ate: false });
+
+        field.fruit = Some(Fruit(field.snake.head, FruitKind::Normal));
+        assert_eq!(field.step(Some(SnakeDirection::Down)), StepOutcome::Continued { ate: true });
+        assert_eq!(field.snake.head, Point::new(6, 6));
+        assert_eq!(field.score(), 1);
+
+        let mut field = field_with_head(10, 10, Point::new(9, 5), SnakeDirection::Right);
+        field.wrap_x = false;
+        assert_eq!(field.step(None), StepOutcome::Died);
+
+        //eating on a 2x1 board grows the snake over the last free cell
+        let mut field = field_with_head(2, 1, Point::new(0, 0), SnakeDirection::Right);
+        field.fruit = Some(Fruit(Point::new(0, 0), FruitKind::Normal));
+        assert_eq!(field.step(None), StepOutcome::Won);
+    }
+
+    #[test]
+    fn directions_have_an_opposite_and_a_delta() {
+        let cases = [
+            (SnakeDirection::Up, SnakeDirection::Down, Point::new(0, -1)),
+            (SnakeDirection::Down, SnakeDirection::Up, Point::new(0, 1)),
+            (SnakeDirection::Left, SnakeDirection::Right, Point::new(-1, 0)),
+            (SnakeDirection::Right, SnakeDirection::Left, Point::new(1, 0)),
+        ];
+
+        for (direction, opposite, delta) in cases {
+            assert_eq!(direction.opposite(), opposite);
+            assert_eq!(direction.delta(), delta);
+            assert_eq!(opposite.delta(), -delta);
+        }
+    }
+
+    #[test]
+    fn start_length_lays_the_tail_out_behind_the_spawn() {
+        let mut field = SnakeGameField::create(10, 10, 1, Box::new(RandomFruitPlacer));
+        field.set_start_length(4).unwrap();
+
+        assert_eq!(field.snake.head, Point::new(5, 5));
+        assert_eq!(field.snake.direction, SnakeDirection::Right);
+        //tail end first, the neck last
+        assert_eq!(field.snake.tail, [Point::new(2, 5), Point::new(3, 5), Point::new(4, 5)]);
+        assert_eq!(field.snake.occupied_cells(), 4);
+
+        field.step(None);
+        field.reset();
+        assert_eq!(field.snake.tail, [Point::new(2, 5), Point::new(3, 5), Point::new(4, 5)]);
+
+        assert!(matches!(field.set_start_length(7), Err(StartLengthError::OffBoard { length: 7, room: 6 })));
+        assert!(matches!(field.set_start_length(0), Err(StartLengthError::Zero)));
+    }
+
+    #[test]
+    fn practice_mode_never_grows_the_snake() {
+        let mut field = SnakeGameField::create(10, 10, 1, Box::new(RandomFruitPlacer));
+        field.practice = true;
+        field.set_start_length(3).unwrap();
+
+        for eaten in 1..=25 {
+            field.fruit = Some(Fruit(field.snake.head, FruitKind::Normal));
+            assert_eq!(field.step(None), StepOutcome::Continued { ate: true });
+            assert_eq!(field.snake.tail.len(), 2);
+            assert_eq!(field.score(), eaten);
+        }
+    }
+
+    #[test]
+    fn try_change_direction_reports_the_outcome() {
+        let mut snake = Snake::new(Point::new(5, 5), SnakeDirection::Right);
+
+        assert_eq!(snake.try_change_direction(SnakeDirection::Right), DirectionChange::Unchanged);
+        assert_eq!(snake.try_change_direction(SnakeDirection::Left), DirectionChange::Rejected);
+        assert_eq!(snake.direction, SnakeDirection::Right);
+
+        assert_eq!(snake.try_change_direction(SnakeDirection::Up), DirectionChange::Applied);
+        assert_eq!(snake.direction, SnakeDirection::Up);
+    }
+
+    //where the fruit is after each of `eats` fruits eaten, the snake teleported onto it every time
+    fn fruit_sequence(field: &mut SnakeGameField, eats: usize) -> Vec<Point> {
+        let mut fruits = Vec::new();
+
+        for _ in 0..eats {
+            let fruit = field.fruit.as_ref().expect("there is room for fruit").0;
+            fruits.push(fruit);
+            field.snake = Snake::new(fruit, field.snake.direction);
+            field.handle_snake_fruit_collision();
+        }
+
+        return fruits;
+    }
+
+    #[test]
+    fn same_seed_spawns_the_same_fruit() {
+        let mut first = SnakeGameField::create(10, 10, 42, Box::new(RandomFruitPlacer));
+        let mut second = SnakeGameField::create(10, 10, 42, Box::new(RandomFruitPlacer));
+        let mut other = SnakeGameField::create(10, 10, 43, Box::new(RandomFruitPlacer));
+
+        let sequence = fruit_sequence(&mut first, 8);
+        assert_eq!(sequence, fruit_sequence(&mut second, 8));
+        assert_ne!(sequence, fruit_sequence(&mut other, 8));
+    }
+
+    #[test]
+    fn first_fruit_can_spawn_in_row_and_column_zero() {
+        //a seed known to put the first fruit in the corner
+        for _ in 0..2 {
+            let field = SnakeGameField::create(10, 10, 37, Box::new(RandomFruitPlacer));
+            assert_eq!(field.fruit.map(|fruit| fruit.0), Some(Point::new(0, 0)));
+        }
+    }
+
+    #[test]
+    fn shuffled_placer_follows_the_seeded_order_among_free_cells() {
+        let snake = Snake::new(Point::new(1, 1), SnakeDirection::Right);
+        let blocked: HashSet<Point> = [Point::new(2, 2)].iter().copied().collect();
+
+        //the same shuffle the placer makes, minus the cells it has to skip
+        let mut expected: Vec<Point> = (0..3).flat_map(|y| (0..4).map(move |x| Point::new(x, y))).collect();
+        expected.shuffle(&mut StdRng::seed_from_u64(5));
+        expected.retain(|cell| !snake.occupies(cell) && !blocked.contains(cell));
+
+        let mut placer = ShuffledFruitPlacer::new();
+        let mut rng = StdRng::seed_from_u64(5);
+        let placed: Vec<Point> = (0..expected.len()).map(|_| placer.place(&snake, &blocked, 4, 3, &mut rng).unwrap().0).collect();
+
+        assert_eq!(placed, expected);
+    }
+
+    #[test]
+    fn fruit_never_spawns_on_the_next_head_cell() {
+        //a small board makes the next head cell a likely pick; (0, 2) is across the wrapping edge
+        for (head, direction) in [(Point::new(1, 1), SnakeDirection::Up), (Point::new(3, 2), SnakeDirection::Right)] {
+            let mut field = field_with_head(4, 4, head, direction);
+
+            for _ in 0..500 {
+                let fruit = field.spawn_fruit().expect("the board has room").0;
+                assert_ne!(fruit, field.next_head());
+            }
+        }
+    }
+
+    #[test]
+    fn last_free_cell_gets_the_fruit() {
+        //(2, 0) is also the next head cell, which is allowed since it's the only free one
+        let mut field = field_with_head(3, 1, Point::new(0, 0), SnakeDirection::Left);
+        field.snake = snake_from_parts(&[Point::new(0, 0), Point::new(1, 0)], SnakeDirection::Left);
+        assert_eq!(field.spawn_fruit().map(|fruit| fruit.0), Some(Point::new(2, 0)));
+
+        field.snake = snake_from_parts(&[Point::new(0, 0), Point::new(1, 0), Point::new(2, 0)], SnakeDirection::Left);
+        assert!(field.spawn_fruit().is_none());
+
+        let mut field = field_with_head(3, 1, Point::new(0, 0), SnakeDirection::Left);
+        field.obstacles.extend([Point::new(1, 0), Point::new(2, 0)]);
+        assert!(field.spawn_fruit().is_none());
+    }
+
+    #[test]
+    fn occupancy_matches_the_snake_after_moves_and_eats() {
+        let mut field = SnakeGameField::create(8, 8, 3, Box::new(RandomFruitPlacer));
+        field.golden_chance = 0.3;
+
+        let mut ate = 0;
+        for _ in 0..300 {
+            let direction = autopilot_direction(&field);
+            match field.step(Some(direction)) {
+                StepOutcome::Continued { ate: true } => ate += 1,
+                StepOutcome::Continued { ate: false } => {}
+                _ => break,
+            }
+
+            let mut expected = HashMap::new();
+            for part in iter::once(&field.snake.head).chain(field.snake.tail.iter()) {
+                *expected.entry(*part).or_insert(0) += 1;
+            }
+            assert_eq!(field.snake.occupied, expected, "at tick {}", field.ticks);
+        }
+
+        assert!(ate >= 5, "only {} fruits eaten", ate);
+    }
+
+    #[test]
+    fn idle_shrink_drops_a_segment_on_schedule_down_to_the_head() {
+        let mut field = field_with_head(10, 10, Point::new(5, 5), SnakeDirection::Right);
+        field.set_start_length(3).unwrap();
+        field.fruit = None;
+        field.idle_shrink = Some(IdleShrink { ticks: 3, fatal: false });
+
+        let mut tail_lengths = Vec::new();
+        for _ in 0..12 {
+            assert_eq!(field.step(None), StepOutcome::Continued { ate: false });
+            tail_lengths.push(field.snake.tail.len());
+        }
+        assert_eq!(tail_lengths, [2, 2, 1, 1, 1, 0, 0, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn fatal_idle_shrink_ends_the_round_once_the_tail_is_gone() {
+        let mut field = field_with_head(10, 10, Point::new(5, 5), SnakeDirection::Right);
+        field.set_start_length(2).unwrap();
+        field.fruit = None;
+        field.idle_shrink = Some(IdleShrink { ticks: 2, fatal: true });
+
+        assert_eq!(field.step(None), StepOutcome::Continued { ate: false });
+        assert_eq!(field.step(None), StepOutcome::Continued { ate: false });
+        assert!(field.snake.tail.is_empty());
+        assert_eq!(field.step(None), StepOutcome::Continued { ate: false });
+        assert_eq!(field.step(None), StepOutcome::Died);
+    }
+
+    struct NeverCollide;
+
+    impl CollisionRule for NeverCollide {
+        fn is_collision(&self, _field: &SnakeGameField, _next_head: &Point) -> Option<DeathCause> {
+            None
+        }
+    }
+
+    #[test]
+    fn custom_collision_rule_lets_the_snake_overlap_itself() {
+        let mut field = field_with_head(10, 10, Point::new(2, 2), SnakeDirection::Down);
+        let parts = [
+            Point::new(2, 2),
+            Point::new(3, 2),
+            Point::new(3, 3),
+            Point::new(2, 3),
+            Point::new(1, 3),
+            Point::new(1, 4),
+        ];
+        field.snake = snake_from_parts(&parts, SnakeDirection::Down);
+        field.collision_rule = Box::new(NeverCollide);
+
+        assert_eq!(field.step(None), StepOutcome::Continued { ate: false });
+        assert_eq!(field.snake.head, Point::new(2, 3));
+        assert_eq!(field.snake.occupied[&Point::new(2, 3)], 2);
+    }
+
+    #[test]
+    fn golden_fruit_scores_and_grows_more() {
+        let mut field = field_with_head(10, 10, Point::new(5, 5), SnakeDirection::Right);
+        field.golden_chance = 1.0;
+
+        field.fruit = Some(Fruit(field.snake.head, FruitKind::Normal));
+        assert_eq!(field.step(None), StepOutcome::Continued { ate: true });
+        assert_eq!((field.score(), field.snake.pending_growth), (1, 0));
+        assert_eq!(field.snake.tail.len(), 1);
+        //with a certain roll the next one is golden, but never two in a row
+        assert_eq!(field.fruit.as_ref().map(|fruit| fruit.1), Some(FruitKind::Golden));
+
+        field.fruit = Some(Fruit(field.snake.head, FruitKind::Golden));
+        assert_eq!(field.step(None), StepOutcome::Continued { ate: true });
+        assert_eq!(field.score(), 1 + GOLDEN_FRUIT_SCORE);
+        assert_eq!(field.snake.pending_growth, GOLDEN_FRUIT_GROWTH - 1);
+        assert_eq!(field.fruit.as_ref().map(|fruit| fruit.1), Some(FruitKind::Normal));
+
+        field.fruit = None;
+        for _ in 0..GOLDEN_FRUIT_GROWTH {
+            field.step(None);
+        }
+        assert_eq!(field.snake.tail.len(), 1 + GOLDEN_FRUIT_GROWTH as usize);
+    }
+
+    #[test]
+    fn each_wrap_mode_wraps_or_stops_at_each_edge() {
+        //start, direction, where a wrap lands, whether that edge is on the x axis
+        let edges = [
+            (Point::new(9, 5), SnakeDirection::Right, Point::new(0, 5), true),
+            (Point::new(0, 5), SnakeDirection::Left, Point::new(9, 5), true),
+            (Point::new(5, 0), SnakeDirection::Up, Point::new(5, 9), false),
+            (Point::new(5, 9), SnakeDirection::Down, Point::new(5, 0), false),
+        ];
+
+        for (wrap_x, wrap_y) in [(false, false), (true, false), (false, true), (true, true)] {
+            for (head, direction, wrapped, x_axis) in edges {
+                let mut field = field_with_head(10, 10, head, direction);
+                field.wrap_x = wrap_x;
+                field.wrap_y = wrap_y;
+
+                let context = format!("wrap ({}, {}) moving {:?}", wrap_x, wrap_y, direction);
+                if (x_axis && wrap_x) || (!x_axis && wrap_y) {
+                    assert_eq!(field.step(None), StepOutcome::Continued { ate: false }, "{}", context);
+                    assert_eq!(field.snake.head, wrapped, "{}", context);
+                } else {
+                    assert_eq!(field.would_collide(&field.next_head()), Some(DeathCause::Wall), "{}", context);
+                    assert_eq!(field.step(None), StepOutcome::Died, "{}", context);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn overlap_during_grace_counts_covered_cells_not_parts() {
+        //three parts on two cells, the head on an obstacle: only (2, 0) is left
+        let mut field = field_with_head(3, 1, Point::new(1, 0), SnakeDirection::Right);
+        field.grace_ticks = 10;
+        field.obstacles.insert(Point::new(1, 0));
+        field.snake = snake_from_parts(&[Point::new(1, 0), Point::new(0, 0), Point::new(0, 0)], SnakeDirection::Right);
+
+        assert_eq!(field.spawn_fruit().map(|fruit| fruit.0), Some(Point::new(2, 0)));
+        assert!(!field.check_win());
+
+        //moving onto the last free cell covers the board, even with the neck still on the obstacle
+        assert_eq!(field.step(None), StepOutcome::Won);
+        assert!(field.spawn_fruit().is_none());
+    }
+
+    #[test]
+    fn autopilot_survives_on_an_empty_board() {
+        //greedy as it is, a long enough snake still coils itself in, so this only covers the early game
+        const TICKS: u64 = 100;
+
+        for (seed, wrap) in [(1, true), (2, true), (3, false), (4, false)] {
+            let mut field = SnakeGameField::create(10, 10, seed, Box::new(RandomFruitPlacer));
+            field.wrap_x = wrap;
+            field.wrap_y = wrap;
+
+            while field.ticks < TICKS {
+                let outcome = field.step(Some(autopilot_direction(&field)));
+                assert!(matches!(outcome, StepOutcome::Continued { .. }), "seed {} ended at tick {}", seed, field.ticks);
+            }
+            assert!(field.score() >= 5, "seed {} only ate {} fruits", seed, field.score());
+        }
+    }
+
+    #[test]
+    fn entering_an_obstacle_is_fatal() {
+        let mut field = field_with_head(10, 10, Point::new(5, 5), SnakeDirection::Right);
+        field.obstacles.insert(Point::new(6, 5));
+
+        assert_eq!(field.would_collide(&field.next_head()), Some(DeathCause::Obstacle));
+        assert_eq!(field.step(None), StepOutcome::Died);
+        assert_eq!(field.snake.head, Point::new(5, 5));
+    }
+
+    #[test]
+    fn fruit_never_spawns_on_an_obstacle() {
+        //every other column walled off, so a placer ignoring obstacles would hit one half the time
+        let mut field = field_with_head(6, 6, Point::new(1, 0), SnakeDirection::Down);
+        field
+            .obstacles
+            .extend((0..6).flat_map(|y| [Point::new(0, y), Point::new(2, y), Point::new(4, y)]));
+
+        for placer in [Box::new(RandomFruitPlacer) as Box<dyn FruitPlacer>, Box::new(ShuffledFruitPlacer::new())] {
+            field.fruit_placer = placer;
+            for _ in 0..200 {
+                let fruit = field.spawn_fruit().expect("the board has room").0;
+                assert!(!field.obstacles.contains(&fruit), "fruit on the obstacle at {:?}", fruit);
+            }
+        }
+    }
+
+    #[test]
+    fn fruit_under_a_tail_segment_is_not_eaten() {
+        let mut field = field_with_head(10, 10, Point::new(5, 5), SnakeDirection::Right);
+        field.snake = snake_from_parts(&[Point::new(5, 5), Point::new(4, 5), Point::new(3, 5)], SnakeDirection::Right);
+        field.fruit = Some(Fruit(Point::new(4, 5), FruitKind::Normal));
+
+        assert_eq!(field.step(None), StepOutcome::Continued { ate: false });
+        assert_eq!((field.score(), field.snake.pending_growth), (0, 0));
+        assert_eq!(field.fruit.as_ref().map(|fruit| fruit.0), Some(Point::new(4, 5)));
+    }
+
+    #[test]
+    fn fruit_never_spawns_under_the_snake() {
+        //a coiled snake covering most of a 4x4 board, leaving 5 free cells
+        let parts = [
+            Point::new(1, 1),
+            Point::new(2, 1),
+            Point::new(2, 2),
+            Point::new(1, 2),
+            Point::new(0, 2),
+            Point::new(0, 1),
+            Point::new(0, 0),
+            Point::new(1, 0),
+            Point::new(2, 0),
+            Point::new(3, 0),
+            Point::new(3, 1),
+        ];
+        let mut field = field_with_head(4, 4, parts[0], SnakeDirection::Down);
+        field.snake = snake_from_parts(&parts, SnakeDirection::Down);
+
+        for placer in [Box::new(RandomFruitPlacer) as Box<dyn FruitPlacer>, Box::new(ShuffledFruitPlacer::new())] {
+            field.fruit_placer = placer;
+            for _ in 0..200 {
+                let fruit = field.spawn_fruit().expect("the board has room").0;
+                assert!(!parts.contains(&fruit), "fruit under the snake at {:?}", fruit);
+            }
+        }
+    }
+
+    #[test]
+    fn would_collide_flags_an_imminent_self_collision() {
+        let mut field = field_with_head(10, 10, Point::new(4, 4), SnakeDirection::Up);
+        let parts = [
+            Point::new(4, 4),
+            Point::new(5, 4),
+            Point::new(5, 3),
+            Point::new(4, 3),
+            Point::new(3, 3),
+            Point::new(3, 2),
+        ];
+        field.snake = snake_from_parts(&parts, SnakeDirection::Up);
+
+        assert_eq!(field.would_collide(&field.next_head()), Some(DeathCause::SelfCollision));
+        assert_eq!(field.would_collide(&field.head_after_move(SnakeDirection::Left)), None);
+        assert_eq!(field.would_collide(&field.head_after_move(SnakeDirection::Down)), None);
+
+        //only a prediction, the snake hasn't moved
+        assert_eq!(field.snake.head, Point::new(4, 4));
+        assert_eq!(field.ticks, 0);
+        assert_eq!(field.step(None), StepOutcome::Died);
+    }
+
+    #[test]
+    fn wraps_within_the_cooldown_cost_a_segment_and_spaced_ones_dont() {
+        //going right along a 3 wide board wraps on every third move: ticks 1, 4, 7
+        let parts = [Point::new(2, 5), Point::new(1, 5), Point::new(0, 5)];
+
+        let mut field = field_with_head(3, 10, parts[0], SnakeDirection::Right);
+        field.snake = snake_from_parts(&parts, SnakeDirection::Right);
+        field.wrap_cooldown = 3;
+        let mut tail_lengths = Vec::new();
+        for _ in 0..7 {
+            field.push_snake();
+            tail_lengths.push(field.snake.tail.len());
+        }
+        assert_eq!(tail_lengths, [2, 2, 2, 1, 1, 1, 0]);
+
+        let mut field = field_with_head(3, 10, parts[0], SnakeDirection::Right);
+        field.snake = snake_from_parts(&parts, SnakeDirection::Right);
+        field.wrap_cooldown = 2;
+        for _ in 0..7 {
+            field.push_snake();
+            assert_eq!(field.snake.tail.len(), 2, "penalized at tick {}", field.ticks);
+        }
+    }
+
+    #[test]
+    fn bounding_box_covers_a_compact_snake() {
+        let snake = snake_from_parts(&[Point::new(3, 3), Point::new(4, 3), Point::new(4, 4), Point::new(3, 4)], SnakeDirection::Up);
+
+        assert_eq!(snake.bounding_box(), (Point::new(3, 3), Point::new(4, 4)));
+        assert_eq!(snake.wrapped_bounding_box(10, 10), (Point::new(3, 3), Point::new(4, 4)));
+    }
+
+    #[test]
+    fn bounding_box_covers_an_l_shaped_snake() {
+        let parts = [
+            Point::new(2, 7),
+            Point::new(2, 6),
+            Point::new(2, 5),
+            Point::new(3, 5),
+            Point::new(4, 5),
+            Point::new(5, 5),
+        ];
+        let snake = snake_from_parts(&parts, SnakeDirection::Down);
+
+        assert_eq!(snake.bounding_box(), (Point::new(2, 5), Point::new(5, 7)));
+        assert_eq!(snake.wrapped_bounding_box(10, 10), (Point::new(2, 5), Point::new(5, 7)));
+
+        //the same L across the left edge: unwrapped it spans the board, wrapped the max corner goes past size_x
+        let wrapped_parts = parts.iter().map(|part| Point::new((part.x + 6) % 10, part.y)).collect::<Vec<Point>>();
+        let snake = snake_from_parts(&wrapped_parts, SnakeDirection::Down);
+
+        assert_eq!(snake.bounding_box(), (Point::new(0, 5), Point::new(9, 7)));
+        assert_eq!(snake.wrapped_bounding_box(10, 10), (Point::new(8, 5), Point::new(11, 7)));
+    }
+}
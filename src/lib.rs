@@ -0,0 +1,11 @@
+// The game logic of snake-rs as a library: the field, the snake and its rules, plus the replay
+// format. Nothing here touches a window or OpenGL, the binary is only a frontend on top of it.
+// Drive a game by calling SnakeGameField::step once per tick:
+//
+// let mut field = SnakeGameField::create(10, 10, seed, Box::new(RandomFruitPlacer));
+// while let StepOutcome::Continued { .. } = field.step(next_input()) {}
+
+pub mod game;
+pub mod replay;
+
+pub use game::{Point, Snake, SnakeDirection, SnakeGameField, StepOutcome};
@@ -4,276 +4,2425 @@ extern crate gl;
 extern crate glfw;
 extern crate rand;
 
+mod audio;
 mod math;
 mod renderer;
+mod text;
+mod theme;
 
+use audio::Audio;
 use gl::types::*;
 use glfw::{Action, Context, Key, OpenGlProfileHint, WindowEvent};
 use math::*;
-use rand::Rng;
 use renderer::*;
-use std::collections::VecDeque;
-use std::ops::{Add, Neg, Sub};
+use snake_rs::game::*;
+use snake_rs::replay::{self, Replay, ReplayRules};
+use std::collections::{HashSet, VecDeque};
+use std::mem;
 use std::os::raw::*;
+use std::path::PathBuf;
 use std::ptr;
+use std::thread;
 use std::time::{Duration, Instant};
-use std::{mem, thread};
+use std::{env, fs, io, iter, process};
+use text::TextRenderer;
+use theme::*;
 
 const WINDOW_WIDTH: u32 = 800;
 const WINDOW_HEIGHT: u32 = 800;
 
 const VERTEX_SHADER_SRC: &str = include_str!("../assets/vertex.glsl");
 const FRAGMENT_SHADER_SRC: &str = include_str!("../assets/fragment.glsl");
+const INSTANCED_VERTEX_SHADER_SRC: &str = include_str!("../assets/vertex_instanced.glsl");
+const INSTANCED_FRAGMENT_SHADER_SRC: &str = include_str!("../assets/fragment_instanced.glsl");
+
+const DANGER_COLOR: Vector4f = Vector4f::new(1.0, 0.55, 0.0, 1.0);
+const GRACE_FLASH_ALPHA: f32 = 0.3;
+const ARROW_COLOR: Vector4f = Vector4f::new(0.1, 0.1, 0.12, 1.0);
+const BUFFERED_ARROW_COLOR: Vector4f = Vector4f::new(0.55, 0.55, 0.6, 1.0);
+const GRID_COLOR: Vector4f = Vector4f::new(0.16, 0.17, 0.2, 1.0);
+const OBSTACLE_COLOR: Vector4f = Vector4f::new(0.52, 0.4, 0.3, 1.0);
+const PAUSE_OVERLAY_COLOR: Vector4f = Vector4f::new(0.0, 0.0, 0.0, 0.55);
+const DEATH_FLASH_COLOR: Vector4f = Vector4f::new(0.95, 0.1, 0.1, 1.0);
+const DEATH_FADE_COLOR: Vector4f = Vector4f::new(0.0, 0.0, 0.0, 0.6); //what the board has faded to once the animation is over
+const DEATH_ANIMATION: Duration = Duration::from_millis(500); //restarting is ignored until it has played
+const HUD_SCORE_SCALE: f32 = 3.0; //font pixel size in window pixels
+const HUD_COUNTDOWN_SCALE: f32 = 16.0;
+const HUD_TITLE_SCALE: f32 = 8.0; //"GAME OVER" and friends
+const HUD_HINT_SCALE: f32 = 3.0;
+const HUD_MARGIN: f32 = 12.0;
+
+//after the theme's snake color, the snake body walks through these stops as the score crosses milestones
+const SNAKE_BODY_GRADIENT_STOPS: [Vector4f; 3] = [
+    Vector4f::new(0.4, 0.9, 0.8, 1.0),
+    Vector4f::new(0.98, 0.78, 0.25, 1.0),
+    Vector4f::new(0.7, 0.45, 0.95, 1.0),
+];
+const SCORE_MILESTONE: usize = 10;
+const SNAKE_BODY_GRADIENT_MILESTONES: usize = 9; //milestones needed to reach the last stop
 
-const SQUARE_COLOR: Vector4f = Vector4f::new(0.26, 0.28, 0.32, 1.0);
-const SNAKE_PART_COLOR: Vector4f = Vector4f::new(1.0, 1.0, 1.0, 1.0);
-const FRUIT_COLOR: Vector4f = Vector4f::new(0.984, 0.11, 0.369, 1.0);
+fn main() {
+    let options = match GameOptions::from_args() {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{}", message);
+            process::exit(2);
+        }
+    };
+
+    if options.bench_game {
+        run_bench_game();
+        return;
+    }
+
+    let playback = match options.replay.as_deref().map(load_replay).transpose() {
+        Ok(playback) => playback,
+        Err(message) => {
+            eprintln!("{}", message);
+            process::exit(2);
+        }
+    };
+
+    let seed = match &playback {
+        Some(replay) => replay.seed,
+        None => options.seed.unwrap_or_else(rand::random),
+    };
+    //playback sets the round up the way it was recorded, whatever the command line says
+    let (board, rules) = match &playback {
+        Some(replay) => (replay.board.clone(), replay.rules.clone()),
+        None => match options.board_layout() {
+            Ok(board) => (board, options.rules()),
+            Err(message) => {
+                eprintln!("{}", message);
+                process::exit(2);
+            }
+        },
+    };
+
+    //the window has to be able to show the board, checked before anything is built for it
+    if options.headless.is_none() {
+        if let Err(message) = options.check_board_fits(&board) {
+            eprintln!("{}", message);
+            process::exit(2);
+        }
+    }
+
+    let mut field = SnakeGameField::from_layout(&board, seed, rules.fruit_placer());
+    if let Err(error) = rules.apply(&mut field) {
+        eprintln!("Invalid start length {}: {}", rules.start_length, error);
+        process::exit(2);
+    }
+
+    //(path, replay) while the first round is being recorded
+    let mut recording = options.record.clone().map(|path| (path, Replay::new(seed, board, rules)));
+
+    if let Some(headless) = &options.headless {
+        run_headless(field, headless, &options, playback.as_ref(), recording);
+        return;
+    }
+
+    //errors are only logged: a failed context creation has to fall through to the next config
+    let mut glfw = glfw::init(glfw::LOG_ERRORS).expect("Unable to init glfw");
+
+    let (mut window, events) = create_window_with_fallback(|config| {
+        glfw.default_window_hints();
+        glfw.window_hint(glfw::WindowHint::ContextVersion(config.major, config.minor));
+        glfw.window_hint(glfw::WindowHint::Resizable(true));
+        glfw.window_hint(glfw::WindowHint::OpenGlProfile(config.profile));
+        #[cfg(target_os = "macos")]
+        glfw.window_hint(glfw::WindowHint::OpenGlForwardCompat(config.profile == OpenGlProfileHint::Core));
+
+        glfw.create_window(WINDOW_WIDTH, WINDOW_HEIGHT, "snake-rs", glfw::WindowMode::Windowed)
+    })
+    .expect("Unable to create window");
+
+    window.set_key_polling(true);
+    window.set_framebuffer_size_polling(true);
+    window.set_mouse_button_polling(options.edit);
+    window.make_current();
+    //only decides about tearing and the frame rate, the game's pace comes from GameClock either way
+    glfw.set_swap_interval(if options.vsync {
+        glfw::SwapInterval::Sync(1)
+    } else {
+        glfw::SwapInterval::None
+    });
+
+    gl::load_with(|symbol| window.get_proc_address(symbol) as *const _);
+
+    let (framebuffer_width, framebuffer_height) = window.get_framebuffer_size();
+    apply_letterbox_viewport(framebuffer_width, framebuffer_height);
+
+    let mut renderer = match SnakeGameRenderer::setup(&options, &field) {
+        Ok(renderer) => renderer,
+        Err(error) => {
+            eprintln!("Unable to set up the renderer: {}", error);
+            process::exit(1);
+        }
+    };
+    renderer.prepare_renderer();
+
+    let audio = if options.mute { None } else { Audio::open() };
+
+    let mut input_queue = InputQueue::new();
+    let mut paused = false;
+    let mut state = GameState::starting();
+    let mut shown_title = None; //the title only changes with the score, the state or the countdown
+    let mut died_at: Option<Instant> = None;
+    let mut high_score = load_high_score();
+
+    let mut clock = GameClock::new(options.cell_step_interval(0) / field.subcell_steps);
+    let mut stats = if options.stats { Some(RateMeter::new()) } else { None };
+
+    loop {
+        if window.should_close() {
+            break;
+        }
+
+        glfw.poll_events();
+
+        let mut exit = false;
+        for (_, event) in glfw::flush_messages(&events) {
+            let key = match event {
+                WindowEvent::Key(key, _, action, _) => handle_input(&options.key_bindings, key, action),
+                WindowEvent::FramebufferSize(width, height) => {
+                    apply_letterbox_viewport(width, height);
+                    None
+                }
+                WindowEvent::MouseButton(glfw::MouseButtonLeft, Action::Press, _) => {
+                    let (x, y) = window.get_cursor_pos();
+                    let cell_size = (renderer.cell_width, renderer.cell_height, renderer.cell_gap);
+                    let board_size = (field.size_x, field.size_y);
+                    let clicked = window_to_cell(
+                        x,
+                        y,
+                        renderer.projection(),
+                        window.get_size(),
+                        window.get_framebuffer_size(),
+                        cell_size,
+                        board_size,
+                    );
+
+                    if let Some(cell) = clicked {
+                        toggle_obstacle(&mut field, cell);
+                    }
+                    None
+                }
+                _ => None,
+            };
+
+            let snake_direction = match key {
+                Some(GameKey::Up) => SnakeDirection::Up,
+                Some(GameKey::Right) => SnakeDirection::Right,
+                Some(GameKey::Down) => SnakeDirection::Down,
+                Some(GameKey::Left) => SnakeDirection::Left,
+                Some(GameKey::Pause) => {
+                    paused = !paused;
+                    continue;
+                }
+                Some(GameKey::Restart) => {
+                    //a replay only holds one round, so it can't be restarted
+                    let dying = matches!(died_at, Some(died_at) if died_at.elapsed() < DEATH_ANIMATION);
+                    let round_over = state == GameState::GameOver || state == GameState::Won;
+                    if round_over && !dying && playback.is_none() {
+                        field.reset();
+                        input_queue.clear();
+                        state = GameState::starting();
+                    }
+                    continue;
+                }
+                Some(GameKey::Exit) => {
+                    exit = true;
+                    break;
+                }
+                None => continue,
+            };
+
+            let snake_direction = if options.rotate_input {
+                //keys follow the rotated board on screen
+                snake_direction.rotated_clockwise(4 - options.rotation_quarter_turns())
+            } else {
+                snake_direction
+            };
+
+            if !options.autopilot && playback.is_none() {
+                input_queue.push(snake_direction, field.snake.direction);
+            }
+        }
+
+        if exit {
+            break;
+        }
+
+        //turns pressed during the countdown stay queued for the first tick
+        if let GameState::Starting { until } = state {
+            if Instant::now() >= until {
+                state = GameState::Playing;
+            }
+        }
+
+        //the clock keeps running while paused so unpausing doesn't fire a backlog of ticks
+        //a cell step takes the current speed's interval, sub-cell ticks just split it up
+        clock.tick_interval = options.cell_step_interval(field.snake.tail.len()) / field.subcell_steps;
+        let due_ticks = clock.tick();
+        let mut ticks = 0;
+        while ticks < due_ticks && state == GameState::Playing && !paused {
+            ticks += 1;
+
+            //sub-cell ticks leave the snake alone, turns wait for the cell step
+            if field.cell_step_due() {
+                if let Some(replay) = &playback {
+                    apply_direction(&mut field.snake, replay.direction_at(field.ticks));
+                } else if options.autopilot {
+                    field.snake.try_change_direction(autopilot_direction(&field));
+                }
+            }
+
+            let outcome = tick_game(&mut field, &mut input_queue, options.input_timing, recording_mut(&mut recording));
+            state = GameState::from(outcome);
+
+            if state != GameState::Playing {
+                finish_recording(&mut recording);
+            }
+
+            if outcome == StepOutcome::Died {
+                died_at = Some(Instant::now());
+            }
+
+            if let Some(audio) = &audio {
+                match outcome {
+                    StepOutcome::Continued { ate: true } => audio.play_eat(),
+                    StepOutcome::Died => audio.play_death(),
+                    _ => {}
+                }
+            }
+
+            //saved once per round, when it ends; autopilot, replayed, practice and edited rounds don't count
+            let counts = !options.autopilot && playback.is_none() && !options.practice && !options.edit;
+            if state != GameState::Playing && counts && field.score() > high_score {
+                high_score = field.score();
+                save_high_score(high_score);
+            }
+        }
+
+        let stats_changed = match &mut stats {
+            Some(stats) => stats.frame(ticks),
+            None => false,
+        };
+
+        let title_state = (field.score(), high_score, state, state.countdown());
+        if shown_title != Some(title_state) || stats_changed {
+            let mut title = window_title(field.score(), high_score, state);
+            if let Some(stats) = &stats {
+                title += &format!(
+                    " — {:.0} FPS, {:.1} TPS ({} ms per tick)",
+                    stats.fps,
+                    stats.tps,
+                    clock.tick_interval.as_millis()
+                );
+            }
+
+            window.set_title(&title);
+            shown_title = Some(title_state);
+        }
+
+        //how far the snake is on its way to the next cell, it rests on its cells while it doesn't move
+        let step_progress = if state == GameState::Playing && !paused {
+            (field.subcell_progress() as f32 + clock.progress()) / field.subcell_steps as f32
+        } else {
+            1.0
+        };
+
+        renderer.render(&field, input_queue.peek(), paused, state == GameState::GameOver, step_progress);
+        renderer.draw_hud(field.score(), high_score, state, paused, playback.is_none());
+
+        window.swap_buffers();
+
+        //without vsync swapping doesn't wait, give the CPU a break between frames
+        if !options.vsync {
+            thread::sleep(UNSYNCED_FRAME_PAUSE);
+        }
+    }
+
+    finish_recording(&mut recording); //quitting mid-round keeps what was played so far
+    if let Some(path) = &options.thumbnail {
+        save_thumbnail(&mut renderer, &field, state == GameState::GameOver, path);
+    }
+    renderer.teardown();
+}
+
+const COUNTDOWN: Duration = Duration::from_secs(3);
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+enum GameState {
+    Starting { until: Instant }, //the board is up but the snake waits for the countdown
+    Playing,
+    GameOver,
+    Won,
+}
+
+impl GameState {
+    //a fresh round, counting down first
+    fn starting() -> GameState {
+        GameState::Starting {
+            until: Instant::now() + COUNTDOWN,
+        }
+    }
+
+    //whole seconds left until the round starts, rounded up
+    fn countdown(&self) -> Option<u64> {
+        match self {
+            GameState::Starting { until } => Some(until.saturating_duration_since(Instant::now()).as_secs_f32().ceil() as u64),
+            _ => None,
+        }
+    }
+}
+
+impl From<StepOutcome> for GameState {
+    fn from(outcome: StepOutcome) -> GameState {
+        match outcome {
+            StepOutcome::Continued { .. } => GameState::Playing,
+            StepOutcome::Died => GameState::GameOver,
+            StepOutcome::Won => GameState::Won,
+        }
+    }
+}
+
+// Advances the game by one cell step, taking the queued turn at the point input_timing says. The
+// recording gets the direction the snake moves in, whatever turned it.
+fn tick_game(field: &mut SnakeGameField, input_queue: &mut InputQueue, input_timing: InputTiming, recording: Option<&mut Replay>) -> StepOutcome {
+    //a sub-cell tick doesn't move the snake, so queued turns and the recording wait for the cell step
+    if !field.cell_step_due() {
+        return field.step(None);
+    }
+
+    if input_timing == InputTiming::StartOfTick {
+        apply_direction(&mut field.snake, input_queue.pop());
+    }
+
+    if let Some(recording) = recording {
+        recording.record(field.ticks, field.snake.direction);
+    }
+
+    let outcome = field.step(None);
+
+    if let StepOutcome::Continued { .. } = outcome {
+        if input_timing == InputTiming::EndOfTick {
+            apply_direction(&mut field.snake, input_queue.pop());
+        }
+    }
+
+    return outcome;
+}
+
+//HEADLESS
+
+// Runs the game without a window for scripts and experiments. Each tick takes the turn from the
+// --replay if there is one, else the next scripted move, otherwise the autopilot's choice with
+// --autopilot, or else it only steers clear of the cell ahead when that would be fatal. The outcome
+// goes to stdout.
+struct HeadlessRun {
+    ticks: u64,
+    moves: Vec<SnakeDirection>,
+}
+
+fn run_headless(mut field: SnakeGameField, run: &HeadlessRun, options: &GameOptions, playback: Option<&Replay>, mut recording: Option<(String, Replay)>) {
+    let mut input_queue = InputQueue::new();
+    let mut moves = run.moves.iter();
+    let mut state = GameState::Playing;
+
+    for _ in 0..run.ticks {
+        //sub-cell ticks leave the snake alone, the next move is taken on the cell step
+        if field.cell_step_due() {
+            //a replay turns the snake directly, it already has the moves exactly as they happened; the
+            //autopilot does too, as in the window, so --input-timing doesn't delay its turns
+            if let Some(replay) = playback {
+                apply_direction(&mut field.snake, replay.direction_at(field.ticks));
+            } else if let Some(direction) = moves.next() {
+                input_queue.push(*direction, field.snake.direction);
+            } else if options.autopilot {
+                field.snake.try_change_direction(autopilot_direction(&field));
+            } else if let Some(direction) = survival_turn(&field) {
+                input_queue.push(direction, field.snake.direction);
+            }
+        }
+
+        state = GameState::from(tick_game(&mut field, &mut input_queue, options.input_timing, recording_mut(&mut recording)));
+        if state != GameState::Playing {
+            break;
+        }
+    }
+
+    finish_recording(&mut recording);
+
+    println!("outcome: {}", outcome_label(state));
+    println!("score: {}", field.score());
+    print!("{}", field.debug_dump());
+}
+
+fn outcome_label(state: GameState) -> &'static str {
+    match state {
+        GameState::Starting { .. } | GameState::Playing => "still playing",
+        GameState::GameOver => "game over",
+        GameState::Won => "won",
+    }
+}
+
+//BENCHMARK
+
+// --bench-game: the autopilot plays a round on a fixed board from a fixed seed until it ends or
+// runs into the tick cap, with nothing drawn. The ticks and score only change along with the game
+// logic, so they tell whether a change to it stays a pure speed-up; the wall time is what it costs.
+const BENCH_SEED: u64 = 2020;
+const BENCH_BOARD_SIZE: i32 = 16;
+const BENCH_TICK_CAP: u64 = 100_000;
+
+#[derive(PartialEq, Debug)]
+struct BenchResult {
+    ticks: u64,
+    score: usize,
+    state: GameState,
+}
+
+fn bench_game() -> BenchResult {
+    let mut field = SnakeGameField::create(BENCH_BOARD_SIZE, BENCH_BOARD_SIZE, BENCH_SEED, Box::new(RandomFruitPlacer));
+    let mut state = GameState::Playing;
+
+    while field.ticks < BENCH_TICK_CAP && state == GameState::Playing {
+        state = GameState::from(field.step(Some(autopilot_direction(&field))));
+    }
+
+    return BenchResult {
+        ticks: field.ticks,
+        score: field.score(),
+        state,
+    };
+}
+
+fn run_bench_game() {
+    let start = Instant::now();
+    let result = bench_game();
+    let elapsed = start.elapsed();
+
+    println!("outcome: {}", outcome_label(result.state));
+    println!("ticks: {}", result.ticks);
+    println!("score: {}", result.score);
+    println!("elapsed: {:.3} ms", elapsed.as_secs_f64() * 1000.0);
+}
+
+//the first of straight on, right or left that doesn't kill the snake, None to keep going
+fn survival_turn(field: &SnakeGameField) -> Option<SnakeDirection> {
+    let direction = field.snake.direction;
+
+    return [0, 1, 3]
+        .iter()
+        .map(|quarter_turns| direction.rotated_clockwise(*quarter_turns))
+        .find(|candidate| field.would_collide(&field.head_after_move(*candidate)).is_none())
+        .filter(|candidate| *candidate != direction);
+}
+
+//"UURDL" style move list
+fn parse_moves(value: &str) -> Result<Vec<SnakeDirection>, String> {
+    value
+        .chars()
+        .map(|c| match c.to_ascii_uppercase() {
+            'U' => Ok(SnakeDirection::Up),
+            'R' => Ok(SnakeDirection::Right),
+            'D' => Ok(SnakeDirection::Down),
+            'L' => Ok(SnakeDirection::Left),
+            _ => Err(format!("Invalid move '{}', expected U, R, D or L", c)),
+        })
+        .collect()
+}
+
+fn window_title(score: usize, high_score: usize, state: GameState) -> String {
+    match state {
+        GameState::Starting { .. } => format!(
+            "snake-rs — Score: {} (best {}) — Starting in {}",
+            score,
+            high_score,
+            state.countdown().unwrap_or(0)
+        ),
+        GameState::Playing => format!("snake-rs — Score: {} (best {})", score, high_score),
+        GameState::GameOver => format!("snake-rs — Score: {} (best {}) — Game over, press Enter to restart", score, high_score),
+        GameState::Won => format!("snake-rs — Score: {} (best {}) — You won! Press Enter to restart", score, high_score),
+    }
+}
+
+//REPLAYS
+
+fn load_replay(path: &str) -> Result<Replay, String> {
+    let bytes = fs::read(path).map_err(|error| format!("Unable to read replay '{}': {}", path, error))?;
+    replay::decode(&bytes).map_err(|error| format!("Invalid replay '{}': {}", path, error))
+}
+
+fn recording_mut(recording: &mut Option<(String, Replay)>) -> Option<&mut Replay> {
+    recording.as_mut().map(|(_, replay)| replay)
+}
+
+//writes a pending recording to its file and stops recording; a failed write is only reported
+fn finish_recording(recording: &mut Option<(String, Replay)>) {
+    if let Some((path, replay)) = recording.take() {
+        if let Err(error) = fs::write(&path, replay::encode(&replay)) {
+            eprintln!("Unable to save replay '{}': {}", path, error);
+        }
+    }
+}
+
+//draws the final board offscreen and writes it as a binary PPM; a failure is only reported
+fn save_thumbnail(renderer: &mut SnakeGameRenderer, field: &SnakeGameField, game_over: bool, path: &str) {
+    let framebuffer = match Framebuffer::create(WINDOW_WIDTH, WINDOW_HEIGHT) {
+        Ok(framebuffer) => framebuffer,
+        Err(status) => {
+            eprintln!("Unable to create the thumbnail framebuffer: status 0x{:X}", status);
+            return;
+        }
+    };
+
+    renderer.render_to_framebuffer(&framebuffer, field, game_over);
+
+    if let Err(error) = fs::write(path, encode_ppm(framebuffer.size(), &framebuffer.read_pixels())) {
+        eprintln!("Unable to save thumbnail '{}': {}", path, error);
+    }
+}
+
+//RGBA rows bottom first, the way GL reads them back, to a PPM with the top row first
+fn encode_ppm((width, height): (u32, u32), pixels: &[u8]) -> Vec<u8> {
+    let mut ppm = format!("P6\n{} {}\n255\n", width, height).into_bytes();
+
+    for row in pixels.chunks(width as usize * 4).rev() {
+        ppm.extend(row.chunks(4).flat_map(|pixel| pixel[..3].iter().copied()));
+    }
+
+    return ppm;
+}
+
+//HIGH SCORE
+
+// The best score lives in a one-line text file under the user's data directory ($XDG_DATA_HOME,
+// else ~/.local/share). With neither set it's only kept for the session.
+fn high_score_path() -> Option<PathBuf> {
+    let data_dir = match env::var_os("XDG_DATA_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".local").join("share"),
+    };
+
+    return Some(data_dir.join("snake-rs").join("highscore"));
+}
+
+fn parse_high_score(contents: &str) -> Option<usize> {
+    contents.trim().parse().ok()
+}
+
+fn format_high_score(score: usize) -> String {
+    format!("{}\n", score)
+}
+
+//a missing file is a first run; an unreadable or garbled one is reported, both count as 0
+fn load_high_score() -> usize {
+    let path = match high_score_path() {
+        Some(path) => path,
+        None => return 0,
+    };
+
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return 0,
+        Err(error) => {
+            eprintln!("Unable to read high score '{}': {}", path.display(), error);
+            return 0;
+        }
+    };
+
+    return parse_high_score(&contents).unwrap_or_else(|| {
+        eprintln!("Ignoring malformed high score file '{}'", path.display());
+        0
+    });
+}
+
+//failing to save only costs the record, the game goes on
+fn save_high_score(score: usize) {
+    let path = match high_score_path() {
+        Some(path) => path,
+        None => return,
+    };
+
+    let saved = match path.parent() {
+        Some(dir) => fs::create_dir_all(dir),
+        None => Ok(()),
+    }
+    .and_then(|_| fs::write(&path, format_high_score(score)));
+
+    if let Err(error) = saved {
+        eprintln!("Unable to save high score to '{}': {}", path.display(), error);
+    }
+}
+
+//WINDOW
+
+struct ContextConfig {
+    major: u32,
+    minor: u32,
+    profile: OpenGlProfileHint,
+}
+
+impl ContextConfig {
+    const fn profile_name(&self) -> &'static str {
+        match self.profile {
+            OpenGlProfileHint::Core => "core",
+            OpenGlProfileHint::Compat => "compatibility",
+            OpenGlProfileHint::Any => "default",
+        }
+    }
+}
+
+// Tried in order. Some drivers and VMs don't offer a 3.3 core profile but still run our
+// GLSL 330 shaders in a compatibility or unspecified profile, which beats not starting at all.
+const CONTEXT_CONFIGS: [ContextConfig; 3] = [
+    ContextConfig {
+        major: 3,
+        minor: 3,
+        profile: OpenGlProfileHint::Core,
+    },
+    ContextConfig {
+        major: 3,
+        minor: 3,
+        profile: OpenGlProfileHint::Compat,
+    },
+    ContextConfig {
+        major: 3,
+        minor: 3,
+        profile: OpenGlProfileHint::Any,
+    },
+];
+
+fn create_window_with_fallback<W>(mut create_window: impl FnMut(&ContextConfig) -> Option<W>) -> Option<W> {
+    for (i, config) in CONTEXT_CONFIGS.iter().enumerate() {
+        if let Some(window) = create_window(config) {
+            if i > 0 {
+                eprintln!("Running with an OpenGL {}.{} {} context", config.major, config.minor, config.profile_name());
+            }
+
+            return Some(window);
+        }
+
+        eprintln!("Unable to create an OpenGL {}.{} {} context", config.major, config.minor, config.profile_name());
+    }
+
+    return None;
+}
+
+// Everything is laid out for a WINDOW_WIDTH x WINDOW_HEIGHT window. After a resize the viewport is
+// the largest area with that aspect ratio which fits the framebuffer, centered, so the projections
+// stay as they are and the field is never stretched; the rest of the window stays black.
+fn letterbox_viewport(framebuffer_width: i32, framebuffer_height: i32) -> (i32, i32, i32, i32) {
+    let scale = (framebuffer_width as f32 / WINDOW_WIDTH as f32).min(framebuffer_height as f32 / WINDOW_HEIGHT as f32);
+    let width = (WINDOW_WIDTH as f32 * scale).round() as i32;
+    let height = (WINDOW_HEIGHT as f32 * scale).round() as i32;
+
+    return ((framebuffer_width - width) / 2, (framebuffer_height - height) / 2, width, height);
+}
+
+fn apply_letterbox_viewport(framebuffer_width: i32, framebuffer_height: i32) {
+    let (x, y, width, height) = letterbox_viewport(framebuffer_width, framebuffer_height);
+    set_viewport(x, y, width, height);
+}
+
+//EDITOR
+
+// Maps a cursor position in window coordinates to the board cell under it, through the inverse of
+// the field projection and the letterboxed viewport. None off the board, in the gap between two
+// cells or over the letterbox bars.
+fn window_to_cell(
+    x: f64,
+    y: f64,
+    projection: &Matrix4f,
+    window_size: (i32, i32),
+    framebuffer_size: (i32, i32),
+    cell_size: (f32, f32, f32),
+    board_size: (i32, i32),
+) -> Option<Point> {
+    if window_size.0 <= 0 || window_size.1 <= 0 {
+        return None; //minimized
+    }
+
+    //window coordinates can differ from framebuffer pixels on high-DPI screens
+    let pixel_x = x as f32 * framebuffer_size.0 as f32 / window_size.0 as f32;
+    let pixel_y = y as f32 * framebuffer_size.1 as f32 / window_size.1 as f32;
+
+    //the viewport is centered, so it's as far from the top as from the bottom
+    let (viewport_x, viewport_y, viewport_width, viewport_height) = letterbox_viewport(framebuffer_size.0, framebuffer_size.1);
+    let ndc_x = 2.0 * (pixel_x - viewport_x as f32) / viewport_width as f32 - 1.0;
+    let ndc_y = 1.0 - 2.0 * (pixel_y - viewport_y as f32) / viewport_height as f32;
+    if ndc_x.abs() > 1.0 || ndc_y.abs() > 1.0 {
+        return None;
+    }
+
+    let local = projection.inverse()? * Vector4f::new(ndc_x, ndc_y, 0.0, 1.0);
+
+    let (cell_width, cell_height, cell_gap) = cell_size;
+    let axis = |position: f32, cell_size: f32, cells: i32| {
+        let index = (position / (cell_size + cell_gap)).floor();
+        let within = position - index * (cell_size + cell_gap);
+        if index < 0.0 || index >= cells as f32 || within > cell_size {
+            return None;
+        }
+        return Some(index as i32);
+    };
+
+    return Some(Point::new(axis(local.x, cell_width, board_size.0)?, axis(local.y, cell_height, board_size.1)?));
+}
+
+//adds an obstacle to a free cell or removes the one there; the snake and the fruit can't be built over
+fn toggle_obstacle(field: &mut SnakeGameField, cell: Point) {
+    if field.obstacles.remove(&cell) {
+        return;
+    }
+
+    let fruit = field.fruit.as_ref().map(|fruit| fruit.0);
+    if field.snake.occupies(&cell) || fruit == Some(cell) {
+        return;
+    }
+
+    field.obstacles.insert(cell);
+}
+
+//OPTIONS
+
+const DEFAULT_HEADLESS_TICKS: u64 = 1000;
+const DEFAULT_BASE_TICK_MS: u64 = 200;
+const DEFAULT_MIN_TICK_MS: u64 = 60;
+const DEFAULT_BOARD_SIZE: i32 = 10;
+const MIN_BOARD_SIZE: i32 = 2;
+const DEFAULT_CELL_SIZE: f32 = 60.0;
+const DEFAULT_CELL_GAP: f32 = 5.0;
+
+struct GameOptions {
+    debug_cells: bool, //report cells drawn more than once per frame
+    checkerboard: bool,
+    input_timing: InputTiming,
+    rotation_degrees: f32, //clockwise, view only
+    rotate_input: bool,
+    shuffled_fruit: bool,
+    grace_ticks: u64, //moves after spawn during which collisions are ignored
+    minimap: bool,
+    zoom: Option<f32>, //follow the head with a camera when set
+    subcell_steps: u32,
+    cell_width: f32,
+    cell_height: f32,
+    cell_gap: f32,     //space between neighbouring cells
+    danger_tint: bool, //tint the head when the next move would be fatal
+    idle_shrink_ticks: Option<u64>,
+    idle_shrink_fatal: bool,
+    golden_chance: f64,  //0 never spawns golden fruit
+    wrap_cooldown: u64,  //moves after a wrap during which another wrap costs a tail segment
+    start_length: usize, //snake parts at the start of a round, head included
+    direction_arrows: bool,
+    gradient: bool,            //the tail darkens towards its end
+    game_over_spin: bool,      //the board spins while the game over screen is up
+    stats: bool,               //frame and tick rate in the window title
+    smooth: bool,              //slide the snake between cells instead of jumping a whole cell per step
+    vsync: bool,               //off renders as fast as it can, with a short pause per frame
+    record: Option<String>,    //replay file the first round is written to
+    replay: Option<String>,    //replay file to play back instead of taking input
+    thumbnail: Option<String>, //PPM file the final board is drawn to on quit
+    key_bindings: KeyBindings,
+    mute: bool,
+    instancing: bool,      //batch the cells into one instanced draw call per view, off falls back to a draw call per cell
+    board: Option<String>, //path of an ASCII board layout to start from
+    board_width: i32,
+    board_height: i32,
+    wrap: (bool, bool),    //per axis, x then y
+    grid: bool,            //draw separators in the gaps between cells
+    theme: Option<String>, //a built-in theme name or a theme file
+    seed: Option<u64>,     //fixes the fruit sequence, random when unset
+    headless: Option<HeadlessRun>,
+    autopilot: bool,  //the snake steers itself towards the fruit
+    bench_game: bool, //time an autopilot round on a fixed board instead of playing
+    practice: bool,   //fruit scores but never grows the snake
+    edit: bool,       //left clicks toggle obstacles
+    base_tick_ms: u64,
+    tick_step_ms: u64, //how much faster each tail segment makes a cell step, 0 keeps the pace constant
+    min_tick_ms: u64,
+}
+
+impl GameOptions {
+    fn from_args() -> Result<GameOptions, String> {
+        let mut options = GameOptions {
+            debug_cells: false,
+            checkerboard: false,
+            input_timing: InputTiming::StartOfTick,
+            rotation_degrees: 0.0,
+            rotate_input: false,
+            shuffled_fruit: false,
+            grace_ticks: 0,
+            minimap: false,
+            zoom: None,
+            subcell_steps: 1,
+            cell_width: DEFAULT_CELL_SIZE,
+            cell_height: DEFAULT_CELL_SIZE,
+            cell_gap: DEFAULT_CELL_GAP,
+            danger_tint: false,
+            idle_shrink_ticks: None,
+            idle_shrink_fatal: false,
+            golden_chance: 0.0,
+            wrap_cooldown: 0,
+            start_length: 1,
+            direction_arrows: false,
+            gradient: false,
+            game_over_spin: false,
+            stats: false,
+            smooth: false,
+            vsync: true,
+            record: None,
+            replay: None,
+            thumbnail: None,
+            key_bindings: KeyBindings::new(),
+            mute: false,
+            instancing: true,
+            board: None,
+            board_width: DEFAULT_BOARD_SIZE,
+            board_height: DEFAULT_BOARD_SIZE,
+            wrap: (true, true),
+            grid: false,
+            theme: None,
+            seed: None,
+            headless: None,
+            autopilot: false,
+            bench_game: false,
+            practice: false,
+            edit: false,
+            base_tick_ms: DEFAULT_BASE_TICK_MS,
+            tick_step_ms: 0,
+            min_tick_ms: DEFAULT_MIN_TICK_MS,
+        };
+
+        let mut args = env::args().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--debug-cells" => options.debug_cells = true,
+                "--checker" | "--checkerboard" => options.checkerboard = true,
+                "--input-timing" => {
+                    options.input_timing = match args.next().as_deref() {
+                        Some("start") => InputTiming::StartOfTick,
+                        Some("end") => InputTiming::EndOfTick,
+                        _ => return Err("--input-timing expects 'start' or 'end'".to_string()),
+                    };
+                }
+                "--rotate" => {
+                    let value = args.next().ok_or("--rotate expects an angle in degrees")?;
+                    options.rotation_degrees = value.parse().map_err(|_| format!("Invalid rotation angle '{}'", value))?;
+                }
+                "--rotate-input" => options.rotate_input = true,
+                "--fruit-order" => {
+                    options.shuffled_fruit = match args.next().as_deref() {
+                        Some("random") => false,
+                        Some("shuffled") => true,
+                        _ => return Err("--fruit-order expects 'random' or 'shuffled'".to_string()),
+                    };
+                }
+                "--grace" => {
+                    let value = args.next().ok_or("--grace expects a number of ticks")?;
+                    options.grace_ticks = value.parse().map_err(|_| format!("Invalid grace period '{}'", value))?;
+                }
+                "--minimap" => options.minimap = true,
+                "--zoom" => {
+                    let value = args.next().ok_or("--zoom expects a zoom factor")?;
+                    let zoom = value.parse::<f32>().ok().filter(|zoom| *zoom > 0.0);
+                    options.zoom = Some(zoom.ok_or(format!("Invalid zoom factor '{}'", value))?);
+                }
+                "--subcells" => {
+                    let value = args.next().ok_or("--subcells expects a number of ticks per cell")?;
+                    let steps = value.parse::<u32>().ok().filter(|steps| *steps > 0);
+                    options.subcell_steps = steps.ok_or(format!("Invalid sub-cell count '{}'", value))?;
+                }
+                "--cell" => {
+                    let value = args.next().ok_or("--cell expects <width>x<height>")?;
+                    let (cell_width, cell_height) = parse_cell_size(&value)?;
+                    options.cell_width = cell_width;
+                    options.cell_height = cell_height;
+                }
+                "--cell-size" => {
+                    let value = args.next().ok_or("--cell-size expects a size in pixels")?;
+                    let size = value.parse::<f32>().ok().filter(|size| *size > 0.0);
+                    let size = size.ok_or(format!("Invalid cell size '{}'", value))?;
+                    options.cell_width = size;
+                    options.cell_height = size;
+                }
+                "--cell-gap" => {
+                    let value = args.next().ok_or("--cell-gap expects a gap in pixels")?;
+                    let gap = value.parse::<f32>().ok().filter(|gap| *gap >= 0.0);
+                    options.cell_gap = gap.ok_or(format!("Invalid cell gap '{}'", value))?;
+                }
+                "--danger-tint" => options.danger_tint = true,
+                "--idle-shrink" => {
+                    let value = args.next().ok_or("--idle-shrink expects a number of ticks")?;
+                    let ticks = value.parse::<u64>().ok().filter(|ticks| *ticks > 0);
+                    options.idle_shrink_ticks = Some(ticks.ok_or(format!("Invalid idle shrink interval '{}'", value))?);
+                }
+                "--idle-shrink-fatal" => options.idle_shrink_fatal = true,
+                "--golden-chance" => {
+                    let value = args.next().ok_or("--golden-chance expects a probability")?;
+                    let chance = value.parse::<f64>().ok().filter(|chance| (0.0..=1.0).contains(chance));
+                    options.golden_chance = chance.ok_or(format!("Invalid golden fruit chance '{}', expected 0 to 1", value))?;
+                }
+                "--wrap-cooldown" => {
+                    let value = args.next().ok_or("--wrap-cooldown expects a number of ticks")?;
+                    options.wrap_cooldown = value.parse().map_err(|_| format!("Invalid wrap cooldown '{}'", value))?;
+                }
+                "--start-length" => {
+                    let value = args.next().ok_or("--start-length expects a number of parts")?;
+                    let length = value.parse().ok().filter(|length| *length > 0);
+                    options.start_length = length.ok_or(format!("Invalid start length '{}'", value))?;
+                }
+                "--direction-arrows" => options.direction_arrows = true,
+                "--gradient" => options.gradient = true,
+                "--game-over-spin" => options.game_over_spin = true,
+                "--stats" => options.stats = true,
+                "--smooth" => options.smooth = true,
+                "--record" => options.record = Some(args.next().ok_or("--record expects a replay file")?),
+                "--replay" => options.replay = Some(args.next().ok_or("--replay expects a replay file")?),
+                "--thumbnail" => options.thumbnail = Some(args.next().ok_or("--thumbnail expects an image file")?),
+                "--vsync" => {
+                    options.vsync = match args.next().as_deref() {
+                        Some("on") => true,
+                        Some("off") => false,
+                        _ => return Err("--vsync expects on or off".to_string()),
+                    };
+                }
+                "--mute" => options.mute = true,
+                "--no-instancing" => options.instancing = false,
+                "--bind" => {
+                    let value = args.next().ok_or("--bind expects <action>=<key>[,<key>...]")?;
+                    let (game_key, keys) = parse_binding(&value)?;
+                    options.key_bindings.bind(game_key, &keys);
+                }
+                "--board" | "--level" => options.board = Some(args.next().ok_or(format!("{} expects a layout file", arg))?),
+                "--width" => options.board_width = parse_board_dimension("--width", args.next())?,
+                "--height" => options.board_height = parse_board_dimension("--height", args.next())?,
+                "--no-wrap" => options.wrap = (false, false),
+                "--wrap" => options.wrap = parse_wrap_mode(args.next().as_deref())?,
+                flag if flag.starts_with("--wrap=") => options.wrap = parse_wrap_mode(flag.strip_prefix("--wrap="))?,
+                "--grid" => options.grid = true,
+                "--theme" => options.theme = Some(args.next().ok_or("--theme expects a theme name or file")?),
+                "--headless" => {
+                    options.headless_run();
+                }
+                "--ticks" => {
+                    let value = args.next().ok_or("--ticks expects a number of ticks")?;
+                    let ticks = value.parse().map_err(|_| format!("Invalid tick count '{}'", value))?;
+                    options.headless_run().ticks = ticks;
+                }
+                "--moves" => {
+                    let value = args.next().ok_or("--moves expects a move list like UURDL")?;
+                    options.headless_run().moves = parse_moves(&value)?;
+                }
+                "--autopilot" => options.autopilot = true,
+                "--bench-game" => options.bench_game = true,
+                "--practice" => options.practice = true,
+                "--edit" => options.edit = true,
+                "--seed" => {
+                    let value = args.next().ok_or("--seed expects a number")?;
+                    options.seed = Some(value.parse().map_err(|_| format!("Invalid seed '{}'", value))?);
+                }
+                "--base-ms" => options.base_tick_ms = parse_tick_ms("--base-ms", args.next())?,
+                "--step-ms" => options.tick_step_ms = parse_tick_ms("--step-ms", args.next())?,
+                "--min-ms" => options.min_tick_ms = parse_tick_ms("--min-ms", args.next())?,
+                _ => eprintln!("Ignoring unknown argument: {}", arg),
+            }
+        }
+
+        //neither a replay nor its recording would know about the edits
+        if options.edit && (options.record.is_some() || options.replay.is_some()) {
+            return Err(String::from("--edit can't be combined with --record or --replay"));
+        }
+
+        return Ok(options);
+    }
+
+    fn board_layout(&self) -> Result<BoardLayout, String> {
+        let path = match &self.board {
+            Some(path) => path,
+            None => return Ok(BoardLayout::empty(self.board_width, self.board_height)),
+        };
+
+        let layout = fs::read_to_string(path).map_err(|error| format!("Unable to read board '{}': {}", path, error))?;
+        return BoardLayout::parse(&layout).map_err(|error| format!("Invalid board '{}': {}", path, error));
+    }
+
+    fn rules(&self) -> ReplayRules {
+        ReplayRules {
+            wrap_x: self.wrap.0,
+            wrap_y: self.wrap.1,
+            shuffled_fruit: self.shuffled_fruit,
+            practice: self.practice,
+            golden_chance: self.golden_chance,
+            start_length: self.start_length,
+            grace_ticks: self.grace_ticks,
+            subcell_steps: self.subcell_steps,
+            idle_shrink: self.idle_shrink_ticks.map(|ticks| IdleShrink {
+                ticks,
+                fatal: self.idle_shrink_fatal,
+            }),
+            wrap_cooldown: self.wrap_cooldown,
+        }
+    }
+
+    //without a camera the whole board is on screen, so it has to fit the window with the configured cell size and gap
+    fn check_board_fits(&self, board: &BoardLayout) -> Result<(), String> {
+        if self.zoom.is_some() {
+            return Ok(());
+        }
+
+        let (width, height) = field_size(board.size_x, board.size_y, self.cell_width, self.cell_height, self.cell_gap);
+        if width > WINDOW_WIDTH as f32 || height > WINDOW_HEIGHT as f32 {
+            return Err(format!(
+                "A {}x{} board takes {}x{} pixels and doesn't fit the {}x{} window, use smaller cells or gaps, or --zoom",
+                board.size_x, board.size_y, width, height, WINDOW_WIDTH, WINDOW_HEIGHT
+            ));
+        }
+
+        return Ok(());
+    }
+
+    //a theme file that can't be loaded only costs the colors, the game still starts with the default theme
+    fn theme(&self) -> Theme {
+        let name = match &self.theme {
+            Some(name) => name,
+            None => return Theme::default(),
+        };
+
+        return Theme::resolve(name).unwrap_or_else(|error| {
+            eprintln!("Unable to load theme '{}' (built-in themes: {}): {}", name, theme_names().join(", "), error);
+            Theme::default()
+        });
+    }
+
+    //--ticks and --moves imply --headless
+    fn headless_run(&mut self) -> &mut HeadlessRun {
+        self.headless.get_or_insert(HeadlessRun {
+            ticks: DEFAULT_HEADLESS_TICKS,
+            moves: Vec::new(),
+        })
+    }
+
+    //the snake speeds up as it grows, but never steps faster than min_tick_ms
+    fn cell_step_interval(&self, tail_length: usize) -> Duration {
+        let speed_up = self.tick_step_ms.saturating_mul(tail_length as u64);
+        Duration::from_millis(self.base_tick_ms.saturating_sub(speed_up).max(self.min_tick_ms))
+    }
+
+    //board rotation rounded to the nearest quarter turn, in 0..4
+    fn rotation_quarter_turns(&self) -> u32 {
+        ((self.rotation_degrees / 90.0).round() as i32).rem_euclid(4) as u32
+    }
+}
+
+fn parse_cell_size(value: &str) -> Result<(f32, f32), String> {
+    let invalid = || format!("Invalid cell size '{}', expected <width>x<height> (e.g. 40x80)", value);
+
+    let (width, height) = value.split_once('x').ok_or_else(invalid)?;
+    let width = width.parse::<f32>().map_err(|_| invalid())?;
+    let height = height.parse::<f32>().map_err(|_| invalid())?;
+
+    if width <= 0.0 || height <= 0.0 {
+        return Err(invalid());
+    }
+
+    return Ok((width, height));
+}
+
+fn parse_tick_ms(flag: &str, value: Option<String>) -> Result<u64, String> {
+    let value = value.ok_or(format!("{} expects a number of milliseconds", flag))?;
+    value.parse().map_err(|_| format!("Invalid {} '{}'", flag, value))
+}
+
+//x, y, both or none: the axes along which leaving the board re-enters on the other side
+fn parse_wrap_mode(value: Option<&str>) -> Result<(bool, bool), String> {
+    match value {
+        Some("x") => Ok((true, false)),
+        Some("y") => Ok((false, true)),
+        Some("both") => Ok((true, true)),
+        Some("none") => Ok((false, false)),
+        _ => Err("--wrap expects 'x', 'y', 'both' or 'none'".to_string()),
+    }
+}
+
+fn parse_board_dimension(flag: &str, value: Option<String>) -> Result<i32, String> {
+    let value = value.ok_or(format!("{} expects a number of cells", flag))?;
+
+    match value.parse::<i32>() {
+        Ok(cells) if (MIN_BOARD_SIZE..=MAX_BOARD_SIZE).contains(&cells) => Ok(cells),
+        _ => Err(format!(
+            "Invalid {} '{}', the board needs {} to {} cells per side",
+            flag, value, MIN_BOARD_SIZE, MAX_BOARD_SIZE
+        )),
+    }
+}
+
+//INPUT
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+enum GameKey {
+    Up,
+    Right,
+    Down,
+    Left,
+    Pause,
+    Restart,
+    Exit,
+}
+
+//StartOfTick turns on the very move after the key press; EndOfTick turns right after a move, so the
+//turn costs up to a tick more but always shows on screen before the snake takes it
+#[derive(PartialEq, Clone, Copy, Debug)]
+enum InputTiming {
+    StartOfTick,
+    EndOfTick,
+}
+
+fn apply_direction(snake: &mut Snake, direction: Option<SnakeDirection>) {
+    if let Some(direction) = direction {
+        snake.try_change_direction(direction);
+    }
+}
+
+const INPUT_QUEUE_CAPACITY: usize = 2;
+
+// Turns pressed faster than the game ticks. One is applied per tick, so tapping Up then Left while
+// heading Right turns up and then left instead of only keeping the last key. Each turn is still
+// validated against the committed direction when it's applied.
+struct InputQueue(VecDeque<SnakeDirection>);
+
+impl InputQueue {
+    fn new() -> InputQueue {
+        InputQueue(VecDeque::with_capacity(INPUT_QUEUE_CAPACITY))
+    }
+
+    //drops repeats and reversals of the turn before it, and presses beyond the capacity
+    fn push(&mut self, direction: SnakeDirection, current_direction: SnakeDirection) {
+        let previous = self.0.back().copied().unwrap_or(current_direction);
+        if direction == previous || direction == previous.opposite() || self.0.len() == INPUT_QUEUE_CAPACITY {
+            return;
+        }
+
+        self.0.push_back(direction);
+    }
+
+    fn pop(&mut self) -> Option<SnakeDirection> {
+        self.0.pop_front()
+    }
+
+    fn clear(&mut self) {
+        self.0.clear();
+    }
+
+    //the turn the next tick will try
+    fn peek(&self) -> Option<SnakeDirection> {
+        self.0.front().copied()
+    }
+}
+
+fn handle_input(key_bindings: &KeyBindings, key: Key, action: Action) -> Option<GameKey> {
+    if action != Action::Press {
+        return Option::None;
+    }
+
+    return key_bindings.resolve(key);
+}
+
+// Which keyboard keys trigger which GameKey. A key triggers at most one GameKey, while a GameKey
+// can have several keys (WASD and the arrows by default).
+struct KeyBindings(Vec<(Key, GameKey)>);
+
+impl KeyBindings {
+    fn new() -> KeyBindings {
+        KeyBindings(vec![
+            (Key::W, GameKey::Up),
+            (Key::Up, GameKey::Up),
+            (Key::D, GameKey::Right),
+            (Key::Right, GameKey::Right),
+            (Key::S, GameKey::Down),
+            (Key::Down, GameKey::Down),
+            (Key::A, GameKey::Left),
+            (Key::Left, GameKey::Left),
+            (Key::Space, GameKey::Pause),
+            (Key::Enter, GameKey::Restart),
+            (Key::Escape, GameKey::Exit),
+        ])
+    }
+
+    //replaces every key of game_key; the new keys are taken away from whatever they triggered before
+    fn bind(&mut self, game_key: GameKey, keys: &[Key]) {
+        self.0.retain(|(key, bound)| *bound != game_key && !keys.contains(key));
+        self.0.extend(keys.iter().map(|key| (*key, game_key)));
+    }
+
+    fn resolve(&self, key: Key) -> Option<GameKey> {
+        self.0.iter().find(|(bound, _)| *bound == key).map(|(_, game_key)| *game_key)
+    }
+}
+
+const GAME_KEY_NAMES: [(&str, GameKey); 7] = [
+    ("up", GameKey::Up),
+    ("right", GameKey::Right),
+    ("down", GameKey::Down),
+    ("left", GameKey::Left),
+    ("pause", GameKey::Pause),
+    ("restart", GameKey::Restart),
+    ("exit", GameKey::Exit),
+];
+
+//the keys --bind knows by name, matched case-insensitively
+const KEY_NAMES: [(&str, Key); 45] = [
+    ("a", Key::A),
+    ("b", Key::B),
+    ("c", Key::C),
+    ("d", Key::D),
+    ("e", Key::E),
+    ("f", Key::F),
+    ("g", Key::G),
+    ("h", Key::H),
+    ("i", Key::I),
+    ("j", Key::J),
+    ("k", Key::K),
+    ("l", Key::L),
+    ("m", Key::M),
+    ("n", Key::N),
+    ("o", Key::O),
+    ("p", Key::P),
+    ("q", Key::Q),
+    ("r", Key::R),
+    ("s", Key::S),
+    ("t", Key::T),
+    ("u", Key::U),
+    ("v", Key::V),
+    ("w", Key::W),
+    ("x", Key::X),
+    ("y", Key::Y),
+    ("z", Key::Z),
+    ("0", Key::Num0),
+    ("1", Key::Num1),
+    ("2", Key::Num2),
+    ("3", Key::Num3),
+    ("4", Key::Num4),
+    ("5", Key::Num5),
+    ("6", Key::Num6),
+    ("7", Key::Num7),
+    ("8", Key::Num8),
+    ("9", Key::Num9),
+    ("up", Key::Up),
+    ("right", Key::Right),
+    ("down", Key::Down),
+    ("left", Key::Left),
+    ("space", Key::Space),
+    ("enter", Key::Enter),
+    ("escape", Key::Escape),
+    ("tab", Key::Tab),
+    ("backspace", Key::Backspace),
+];
+
+//"<action>=<key>[,<key>...]", e.g. "up=i,up"
+fn parse_binding(value: &str) -> Result<(GameKey, Vec<Key>), String> {
+    fn lookup<T: Copy>(name: &str, table: &[(&str, T)]) -> Option<T> {
+        table.iter().find(|(known, _)| known.eq_ignore_ascii_case(name.trim())).map(|(_, item)| *item)
+    }
+
+    let (action, keys) = value
+        .split_once('=')
+        .ok_or(format!("Invalid binding '{}', expected <action>=<key>[,<key>...]", value))?;
+    let game_key = lookup(action, &GAME_KEY_NAMES).ok_or(format!("Unknown action '{}' in binding '{}'", action, value))?;
+
+    let keys = keys
+        .split(',')
+        .map(|key| lookup(key, &KEY_NAMES).ok_or(format!("Unknown key '{}' in binding '{}'", key, value)))
+        .collect::<Result<Vec<Key>, String>>()?;
+
+    return Ok((game_key, keys));
+}
+
+//CLOCK
+
+const UNSYNCED_FRAME_PAUSE: Duration = Duration::from_millis(1);
+const MAX_FRAME_DELTA: Duration = Duration::from_millis(250); //longer frames (a stall, a suspended process) count as this long
+const MIN_TICK_INTERVAL: Duration = Duration::from_millis(1);
+
+// Fixed-timestep pacing: frames run at whatever rate vsync allows (or as fast as UNSYNCED_FRAME_PAUSE
+// lets them with --vsync off), the game ticks once for every whole tick_interval that has built up.
+// A slow frame catches up on all of its ticks, but a frame adds at most MAX_FRAME_DELTA, so after a
+// stall the snake only makes the few moves that fit in it instead of jumping across the board.
+struct GameClock {
+    tick_interval: Duration,
+    accumulator: Duration,
+    last_frame: Instant,
+}
+
+impl GameClock {
+    fn new(tick_interval: Duration) -> GameClock {
+        GameClock {
+            tick_interval,
+            accumulator: Duration::ZERO,
+            last_frame: Instant::now(),
+        }
+    }
+
+    //call once per frame, returns how many times the game should tick
+    fn tick(&mut self) -> u32 {
+        let now = Instant::now();
+        let delta = now - self.last_frame;
+        self.last_frame = now;
+
+        return self.advance(delta);
+    }
+
+    //adds one frame's worth of time, returns the number of ticks that came due
+    fn advance(&mut self, delta: Duration) -> u32 {
+        self.accumulator += delta.min(MAX_FRAME_DELTA);
+
+        //a zero interval would never stop ticking
+        let tick_interval = self.tick_interval.max(MIN_TICK_INTERVAL);
+
+        let mut ticks = 0;
+        while self.accumulator >= tick_interval {
+            self.accumulator -= tick_interval;
+            ticks += 1;
+        }
+
+        return ticks;
+    }
+
+    //how much of the next tick has built up, in 0..=1
+    fn progress(&self) -> f32 {
+        (self.accumulator.as_secs_f32() / self.tick_interval.as_secs_f32()).min(1.0)
+    }
+}
+
+const RATE_WINDOW: Duration = Duration::from_millis(500);
+
+// Frames and game ticks per second, averaged over RATE_WINDOW so the numbers are readable
+// instead of jumping with every frame.
+struct RateMeter {
+    window_start: Instant,
+    frames: u32,
+    ticks: u32,
+    fps: f32,
+    tps: f32,
+}
+
+impl RateMeter {
+    fn new() -> RateMeter {
+        RateMeter {
+            window_start: Instant::now(),
+            frames: 0,
+            ticks: 0,
+            fps: 0.0,
+            tps: 0.0,
+        }
+    }
+
+    //call once per frame with the number of game ticks it ran, true when the rates were updated
+    fn frame(&mut self, ticks: u32) -> bool {
+        self.frames += 1;
+        self.ticks += ticks;
+
+        let elapsed = self.window_start.elapsed();
+        if elapsed < RATE_WINDOW {
+            return false;
+        }
+
+        let seconds = elapsed.as_secs_f32();
+        self.fps = self.frames as f32 / seconds;
+        self.tps = self.ticks as f32 / seconds;
+
+        self.window_start = Instant::now();
+        self.frames = 0;
+        self.ticks = 0;
+        return true;
+    }
+}
+
+struct SnakeGameRenderer {
+    vbo: BufferObject,
+    ebo: BufferObject,
+    square: VertexArrayObject,
+    quads: Box<[VertexArrayObject]>,
+    shader_program: ShaderProgram,
+    color_uniform: UniformLocation,
+    projection_uniform: UniformLocation,
+    projection: Matrix4f, //field-local coordinates to clip space, follows the camera if there is one
+    camera: Option<Camera>,
+    rotation: f32,
+    size_x: i32, //board size in cells
+    size_y: i32,
+    cell_width: f32,
+    cell_height: f32,
+    cell_gap: f32,
+    minimap_projection: Option<Matrix4f>,
+    drawn_cells: Option<HashSet<Point>>, //only tracked in debug cells mode
+    cells: Vec<(Point, CellContent)>,    //draw_field's cell list, reused between frames
+    checkerboard: bool,
+    grid: bool,
+    danger_tint: bool,
+    direction_arrows: bool,
+    gradient: bool,
+    game_over_spin: bool,
+    spin_start: Option<Instant>,                //when the current game over spin began
+    death_start: Option<Instant>,               //when the current death animation began
+    death_progress: Option<f32>,                //per-frame: how far the death animation got, None while alive
+    motion: Option<SnakeMotion>,                //only with --smooth
+    step_progress: f32,                         //per-frame: 0 at the previous cell step, 1 at the next
+    buffered_direction: Option<SnakeDirection>, //per-frame: the turn waiting to be applied
+    start: Instant,                             //animation clock
+    theme: Theme,
+    snake_gradient: [Vector4f; 4], //the theme's snake color followed by SNAKE_BODY_GRADIENT_STOPS
+    cell_batch: Option<CellBatch>, //None draws every cell on its own
+    text: TextRenderer,
+}
+
+impl SnakeGameRenderer {
+    //fails with the shader info log if the shaders don't compile or link
+    fn setup(options: &GameOptions, field: &SnakeGameField) -> Result<SnakeGameRenderer, RendererError> {
+        let shader_program = create_shader_program(VERTEX_SHADER_SRC, FRAGMENT_SHADER_SRC)?;
+
+        let (size_x, size_y) = (field.size_x, field.size_y);
+
+        let vertices = gen_vertices(size_x, size_y, options.cell_width, options.cell_height, options.cell_gap);
+        let (vbo, ebo) = gen_buffer_objects(vertices.as_slice());
+        let (square, quads) = gen_vertex_array_objects(&vbo, &ebo, (size_x * size_y) as usize);
+
+        //every cell can show up twice in one flush: a checkerboard cell under a snake part, fruit or obstacle
+        let cell_batch = if options.instancing {
+            Some(CellBatch::setup(&vbo, &ebo, 2 * (size_x * size_y) as usize)?)
+        } else {
+            None
+        };
+
+        let text = TextRenderer::setup(WINDOW_WIDTH, WINDOW_HEIGHT)?;
+
+        let color_uniform = UniformLocation::get(&shader_program, "inColor");
+        let projection_uniform = UniformLocation::get(&shader_program, "projection");
+
+        let rotation = options.rotation_degrees.to_radians();
+        let projection = field_projection(size_x, size_y, options.cell_width, options.cell_height, options.cell_gap, rotation);
+
+        let theme = options.theme();
+        let [teal, amber, violet] = SNAKE_BODY_GRADIENT_STOPS;
+        let snake_gradient = [theme.snake.clone(), teal, amber, violet];
+
+        return Ok(SnakeGameRenderer {
+            vbo,
+            ebo,
+            square,
+            quads,
+            shader_program,
+            color_uniform,
+            projection_uniform,
+            projection,
+            camera: options.zoom.map(Camera::new),
+            rotation,
+            size_x,
+            size_y,
+            cell_width: options.cell_width,
+            cell_height: options.cell_height,
+            cell_gap: options.cell_gap,
+            minimap_projection: if options.minimap {
+                Some(minimap_projection(size_x, size_y, options.cell_width, options.cell_height, options.cell_gap))
+            } else {
+                None
+            },
+            drawn_cells: if options.debug_cells { Some(HashSet::new()) } else { None },
+            cells: Vec::new(),
+            checkerboard: options.checkerboard,
+            grid: options.grid,
+            danger_tint: options.danger_tint,
+            direction_arrows: options.direction_arrows,
+            gradient: options.gradient,
+            game_over_spin: options.game_over_spin,
+            spin_start: None,
+            death_start: None,
+            death_progress: None,
+            motion: if options.smooth { Some(SnakeMotion::new()) } else { None },
+            step_progress: 1.0,
+            buffered_direction: None,
+            start: Instant::now(),
+            theme,
+            snake_gradient,
+            cell_batch,
+            text,
+        });
+    }
+
+    // Frees the GL objects while the context is still current and reports any GL errors. Every
+    // wrapper deletes its object on drop, this only fixes the order: VAOs go first since they
+    // reference the buffers, the program goes last; its shaders were already detached and deleted
+    // right after linking (see create_shader_program). The cell batch and the text renderer bring
+    // their own VAO, buffer and program and go with the VAOs.
+    fn teardown(self) {
+        unbind_vao();
+        unbind_program();
+
+        let SnakeGameRenderer {
+            vbo,
+            ebo,
+            square,
+            quads,
+            shader_program,
+            cell_batch,
+            text,
+            ..
+        } = self;
+
+        drop(quads);
+        drop(square);
+        drop(cell_batch);
+        drop(text);
+
+        drop(vbo);
+        drop(ebo);
+
+        drop(shader_program);
+
+        for error in take_gl_errors() {
+            eprintln!("GL error 0x{:X} while tearing down the renderer", error);
+        }
+    }
+
+    fn projection(&self) -> &Matrix4f {
+        &self.projection
+    }
+
+    fn prepare_renderer(&self) {
+        self.shader_program.use_program();
+        set_clear_color(&self.theme.background);
+    }
+
+    // Draws every view of the field in two passes. The opaque pass runs with blending off; the
+    // transparent pass runs afterwards with blending on and walks the views back to front, so a
+    // translucent part is always composited over everything beneath it. There is no depth buffer,
+    // draw order alone decides what ends up on top.
+    fn render(&mut self, field: &SnakeGameField, buffered_direction: Option<SnakeDirection>, paused: bool, game_over: bool, step_progress: f32) {
+        if let Some(drawn_cells) = &mut self.drawn_cells {
+            drawn_cells.clear();
+        }
+        self.buffered_direction = buffered_direction;
+        self.step_progress = step_progress;
+        if let Some(motion) = &mut self.motion {
+            motion.update(field);
+        }
+        self.death_progress = self.death_progress(game_over);
+
+        //the background darkens along with the board while dying
+        let fade = self.death_progress.unwrap_or(0.0) * DEATH_FADE_COLOR.w;
+        let mut background = self.theme.background.lerp(&DEATH_FADE_COLOR, fade);
+        background.w = self.theme.background.w;
+        set_clear_color(&background);
+        clear_color_buffer();
+
+        //(projection, whether overdraw is tracked) of every view, back to front
+        let mut views = Vec::with_capacity(10);
+
+        if let Some(camera) = &mut self.camera {
+            let head = &field.snake.head;
+            let head_center = (
+                head.x as f32 * (self.cell_width + self.cell_gap) + self.cell_width / 2.0,
+                head.y as f32 * (self.cell_height + self.cell_gap) + self.cell_height / 2.0,
+            );
+
+            let field_size = field_size(self.size_x, self.size_y, self.cell_width, self.cell_height, self.cell_gap);
+            let period = (field_size.0 + self.cell_gap, field_size.1 + self.cell_gap);
+
+            let wrap = (field.wrap_x, field.wrap_y);
+            let center = camera.follow(head_center, field_size, period, wrap);
+            self.projection = view_projection(center, camera.zoom, self.rotation);
+
+            //surround a wrapping field with its copies along the wrapping axes, so the view never shows the outside there
+            let copies = |wrap: bool| if wrap { -1..=1 } else { 0..=0 };
+            for (i, j) in copies(wrap.0).flat_map(|i| copies(wrap.1).map(move |j| (i, j))).filter(|tile| *tile != (0, 0)) {
+                let offset = Vector3f::new(i as f32 * period.0, j as f32 * period.1, 0.0);
+                views.push((self.projection.clone().translate(&offset), false));
+            }
+        }
+
+        views.push((self.projection.clone(), true));
+
+        if let Some(spin) = self.game_over_spin(game_over) {
+            for (projection, _) in &mut views {
+                *projection = projection.clone() * spin.clone();
+            }
+        }
+
+        if let Some(minimap_projection) = &self.minimap_projection {
+            views.push((minimap_projection.clone(), false)); //the minimap draws every cell a second time
+        }
+
+        for &pass in RENDER_PASSES.iter() {
+            set_blending(pass.blends());
+
+            for (projection, tracked) in &views {
+                self.shader_program.set_uniform_mat4(&self.projection_uniform, projection);
+
+                let drawn_cells = if *tracked { None } else { self.drawn_cells.take() };
+                self.draw_field(field, pass, projection);
+                if !*tracked {
+                    self.drawn_cells = drawn_cells;
+                }
+            }
+        }
+
+        //blending is still on from the transparent pass
+        if let Some(progress) = self.death_progress {
+            let mut fade_color = DEATH_FADE_COLOR;
+            fade_color.w *= progress;
+            self.draw_overlay(&fade_color);
+        }
+
+        //dim everything while paused
+        if paused {
+            self.draw_overlay(&PAUSE_OVERLAY_COLOR);
+        }
+    }
+
+    // Draws the field into `framebuffer` instead of the window, through the same views and with the
+    // same animations as on screen but without the HUD. The viewport is left at the framebuffer's
+    // size, a caller that goes on drawing to the window sets its own again.
+    fn render_to_framebuffer(&mut self, framebuffer: &Framebuffer, field: &SnakeGameField, game_over: bool) {
+        let (width, height) = framebuffer.size();
+
+        framebuffer.bind();
+        set_viewport(0, 0, width as i32, height as i32);
+        self.render(field, None, false, game_over, 1.0);
+        unbind_framebuffer();
+    }
+
+    // The text over the board: the score in the corner, the countdown before a round and a banner
+    // once it's over. The restart hint shows once Enter would actually restart. Text takes the
+    // snake's color, which every theme keeps readable on the board.
+    fn draw_hud(&mut self, score: usize, high_score: usize, state: GameState, paused: bool, restartable: bool) {
+        let width = WINDOW_WIDTH as f32;
+        let height = WINDOW_HEIGHT as f32;
+        let color = self.theme.snake.clone();
+
+        let score_text = format!("SCORE {}  BEST {}", score, high_score);
+        self.text.draw_text(&score_text, HUD_MARGIN, HUD_MARGIN, HUD_SCORE_SCALE, &color);
+
+        let banner = match state {
+            GameState::Starting { .. } => state.countdown().map(|seconds| (seconds.to_string(), HUD_COUNTDOWN_SCALE, color.clone())),
+            GameState::Playing if paused => Some((String::from("PAUSED"), HUD_TITLE_SCALE, color.clone())),
+            GameState::Playing => None,
+            GameState::GameOver => Some((String::from("GAME OVER"), HUD_TITLE_SCALE, DEATH_FLASH_COLOR)),
+            GameState::Won => Some((String::from("YOU WIN!"), HUD_TITLE_SCALE, self.theme.snake_head.clone())),
+        };
+
+        if let Some((text, scale, banner_color)) = banner {
+            let y = (height - TextRenderer::text_height(scale)) / 2.0;
+            self.text
+                .draw_text(&text, (width - TextRenderer::text_width(&text, scale)) / 2.0, y, scale, &banner_color);
+
+            let over = match state {
+                GameState::GameOver => matches!(self.death_progress, Some(progress) if progress >= 1.0),
+                GameState::Won => true,
+                _ => false,
+            };
+            if over && restartable {
+                let hint = "PRESS ENTER TO RESTART";
+                let hint_y = y + TextRenderer::text_height(scale) + TextRenderer::text_height(HUD_HINT_SCALE);
+                self.text.draw_text(
+                    hint,
+                    (width - TextRenderer::text_width(hint, HUD_HINT_SCALE)) / 2.0,
+                    hint_y,
+                    HUD_HINT_SCALE,
+                    &color,
+                );
+            }
+        }
+
+        self.shader_program.use_program(); //the board's program is expected in use again next frame
+    }
+
+    //covers the whole window in a translucent color, blending has to be on
+    fn draw_overlay(&mut self, color: &Vector4f) {
+        let (field_width, field_height) = field_size(self.size_x, self.size_y, self.cell_width, self.cell_height, self.cell_gap);
+        let fullscreen = ortho2d(0.0, field_width, field_height, 0.0); //stretches the field square over the window
+
+        self.shader_program.set_uniform_mat4(&self.projection_uniform, &fullscreen);
+        self.shader_program.set_uniform_vec4(&self.color_uniform, color);
+        self.square.draw_elements(QUAD_INDEX_COUNT);
+    }
+
+    //0 right after dying up to 1 once DEATH_ANIMATION is over, None while the game is on
+    fn death_progress(&mut self, game_over: bool) -> Option<f32> {
+        if !game_over {
+            self.death_start = None;
+            return None;
+        }
+
+        let elapsed = self.death_start.get_or_insert_with(Instant::now).elapsed();
+        return Some((elapsed.as_secs_f32() / DEATH_ANIMATION.as_secs_f32()).min(1.0));
+    }
+
+    //turns the field around its center for as long as the game is over, None otherwise
+    fn game_over_spin(&mut self, game_over: bool) -> Option<Matrix4f> {
+        if !self.game_over_spin || !game_over {
+            self.spin_start = None;
+            return None;
+        }
+
+        let seconds = self.spin_start.get_or_insert_with(Instant::now).elapsed().as_secs_f32();
+        let (field_width, field_height) = field_size(self.size_x, self.size_y, self.cell_width, self.cell_height, self.cell_gap);
+
+        let center = Vector3f::new(field_width / 2.0, field_height / 2.0, 0.0);
+
+        let spin = Matrix4f::identity().translate(&center) * Matrix4f::rotation_z(seconds * GAME_OVER_SPIN_SPEED);
+        return Some(spin.translate(&-center));
+    }
+
+    //draws a cell right away, or queues it for flush_cells when batching; cells outside the grid are skipped
+    fn draw_cell(&mut self, cell: &Point, color: &Vector4f) {
+        let index = match quad_index(cell, self.size_x, self.size_y) {
+            Some(index) => index,
+            None => return,
+        };
+
+        match &mut self.cell_batch {
+            Some(cell_batch) => cell_batch.push(cell_origin(cell, self.cell_width, self.cell_height, self.cell_gap), color),
+            None => {
+                self.shader_program.set_uniform_vec4(&self.color_uniform, color);
+                self.quads[index].draw_elements(QUAD_INDEX_COUNT);
+            }
+        }
+    }
+
+    //like draw_cell, but anywhere on the field rather than on a cell, `origin` is the top left corner
+    fn draw_quad_at(&mut self, origin: (f32, f32), color: &Vector4f, projection: &Matrix4f) {
+        match &mut self.cell_batch {
+            Some(cell_batch) => cell_batch.push(origin, color),
+            None => {
+                //the first cell's quad starts at the field's corner, so it only has to be moved
+                let moved = projection.clone().translate(&Vector3f::new(origin.0, origin.1, 0.0));
+                self.shader_program.set_uniform_mat4(&self.projection_uniform, &moved);
+                self.shader_program.set_uniform_vec4(&self.color_uniform, color);
+                self.quads[0].draw_elements(QUAD_INDEX_COUNT);
+                self.shader_program.set_uniform_mat4(&self.projection_uniform, projection);
+            }
+        }
+    }
+
+    //draws the queued cells, must run before anything that has to end up on top of them
+    fn flush_cells(&mut self, projection: &Matrix4f) {
+        if let Some(cell_batch) = &mut self.cell_batch {
+            if cell_batch.flush(projection) {
+                self.shader_program.use_program();
+            }
+        }
+    }
+
+    fn draw_field(&mut self, field: &SnakeGameField, pass: RenderPass, projection: &Matrix4f) {
+        let snake_head = &field.snake.head;
+
+        if pass == RenderPass::Opaque {
+            //RENDER FIELD SQUARE
+            self.shader_program.set_uniform_vec4(&self.color_uniform, &self.theme.square);
+
+            self.square.draw_elements(QUAD_INDEX_COUNT);
+
+            //RENDER CHECKERBOARD CELLS
+            if self.checkerboard {
+                let shades = [self.theme.checker_dark.clone(), self.theme.checker_light.clone()];
+                let size_x = field.size_x;
+                for cell in (0..size_x * field.size_y).map(|i| Point::new(i % size_x, i / size_x)) {
+                    self.draw_cell(&cell, &shades[checker_shade(&cell)]);
+                }
+            }
+
+            //RENDER GRID LINES
+            if self.grid {
+                self.flush_cells(projection);
+                self.shader_program.set_uniform_vec4(&self.color_uniform, &GRID_COLOR);
+
+                //every gap is the field square squashed down to cell_gap across
+                let (cell_width, cell_height, cell_gap) = (self.cell_width, self.cell_height, self.cell_gap);
+                let (field_width, field_height) = field_size(field.size_x, field.size_y, cell_width, cell_height, cell_gap);
+                let vertical = (1..field.size_x).map(|x| {
+                    let offset = Vector3f::new(x as f32 * (cell_width + cell_gap) - cell_gap, 0.0, 0.0);
+                    (offset, Vector3f::new(cell_gap / field_width, 1.0, 1.0))
+                });
+                let horizontal = (1..field.size_y).map(|y| {
+                    let offset = Vector3f::new(0.0, y as f32 * (cell_height + cell_gap) - cell_gap, 0.0);
+                    (offset, Vector3f::new(1.0, cell_gap / field_height, 1.0))
+                });
+
+                for (offset, scale) in vertical.chain(horizontal) {
+                    let line_projection = projection.clone().translate(&offset).scale(&scale);
+                    self.shader_program.set_uniform_mat4(&self.projection_uniform, &line_projection);
+                    self.square.draw_elements(QUAD_INDEX_COUNT);
+                }
+
+                self.shader_program.set_uniform_mat4(&self.projection_uniform, projection);
+            }
+        }
+
+        //RENDER OBSTACLES, FRUIT AND SNAKE
+        let mut snake_color = score_milestone_color(field.score(), &self.snake_gradient);
+        if field.in_grace_period() && field.ticks % 2 == 1 {
+            snake_color.w = GRACE_FLASH_ALPHA; //flashing while invulnerable
+        }
+
+        //warn the tick before a fatal move
+        let danger = self.danger_tint && field.check_snake_collision();
+        let mut head_color = if danger { DANGER_COLOR } else { self.theme.snake_head.clone() };
+        head_color.w = snake_color.w;
 
-fn main() {
-    let mut glfw = glfw::init(glfw::FAIL_ON_ERRORS).expect("Unable to init glfw");
+        //a dead snake flashes red and settles back to its colors while the board fades out
+        if let Some(progress) = self.death_progress {
+            snake_color = DEATH_FLASH_COLOR.lerp(&snake_color, progress);
+            head_color = DEATH_FLASH_COLOR.lerp(&head_color, progress);
+        }
 
-    glfw.window_hint(glfw::WindowHint::ContextVersion(3, 3));
-    glfw.window_hint(glfw::WindowHint::Resizable(false));
-    glfw.window_hint(glfw::WindowHint::OpenGlProfile(OpenGlProfileHint::Core));
-    #[cfg(target_os = "macos")]
-    glfw.window_hint(glfw::WindowHint::OpenGlForwardCompat(true));
+        //with --gradient the tail runs from the body color at the neck to a darker shade at its end
+        let mut tail_tip_color = if self.gradient { &snake_color * TAIL_TIP_SHADE } else { snake_color.clone() };
+        tail_tip_color.w = snake_color.w;
+        let tail_length = field.snake.tail.len() as f32;
 
-    let (mut window, events) = glfw
-        .create_window(WINDOW_WIDTH, WINDOW_HEIGHT, "snake-rs", glfw::WindowMode::Windowed)
-        .expect("Unable to create window");
+        let seconds = self.start.elapsed().as_secs_f32();
+        let (cell_width, cell_height, cell_gap) = (self.cell_width, self.cell_height, self.cell_gap);
 
-    window.set_key_polling(true);
-    window.make_current();
+        let mut cells = mem::take(&mut self.cells);
+        field_cells(field, pass, &mut cells);
 
-    gl::load_with(|symbol| window.get_proc_address(symbol) as *const _);
+        for (cell, content) in &cells {
+            let (color, part) = match content {
+                CellContent::Obstacle => (OBSTACLE_COLOR, None),
+                CellContent::Fruit(FruitKind::Normal) => (pulsed_color(&self.theme.fruit, seconds), None),
+                CellContent::Fruit(FruitKind::Golden) => (pulsed_color(&GOLDEN_FRUIT_COLOR, seconds * GOLDEN_FRUIT_PULSE_SPEEDUP), None),
+                CellContent::SnakeHead => (head_color.clone(), Some(0)),
+                CellContent::SnakeTail(position) => (snake_color.lerp(&tail_tip_color, *position as f32 / tail_length), Some(*position)),
+            };
 
-    let mut field = SnakeGameField::create();
+            if field.in_bounds(cell) {
+                track_cell(&mut self.drawn_cells, cell);
+            }
 
-    let mut renderer = SnakeGameRenderer::setup();
-    renderer.prepare_renderer();
+            let slide_origin = match (&self.motion, part) {
+                (Some(motion), Some(part)) if field.in_bounds(cell) => motion.origin(part, self.step_progress, cell_width, cell_height, cell_gap),
+                _ => None,
+            };
 
-    loop {
-        if window.should_close() {
-            break;
+            match slide_origin {
+                Some(origin) => self.draw_quad_at(origin, &color, projection),
+                None => self.draw_cell(cell, &color),
+            }
         }
 
-        glfw.poll_events();
+        let snake_drawn = cells.iter().any(|(_, content)| *content == CellContent::SnakeHead);
+        self.cells = cells;
 
-        let mut pressed_key = None;
-        for (_, event) in glfw::flush_messages(&events) {
-            if let WindowEvent::Key(key, _, action, _) = event {
-                pressed_key = handle_input(key, action).or(pressed_key);
-            }
+        self.flush_cells(projection);
+
+        //RENDER DIRECTION ARROWS
+        if !snake_drawn || !self.direction_arrows {
+            return;
         }
 
-        match pressed_key {
-            Some(key) => {
-                let snake_direction = match key {
-                    GameKey::Up => SnakeDirection::Up,
-                    GameKey::Right => SnakeDirection::Right,
-                    GameKey::Down => SnakeDirection::Down,
-                    GameKey::Left => SnakeDirection::Left,
-                    GameKey::Exit => break,
-                };
+        // A small square at the head's edge for the direction the snake moves in, and a lighter one
+        // for a buffered turn, so it's visible why a quick double turn didn't register as a diagonal.
+        // Both reuse the first cell's quad, moved and shrunk by a model transform on the projection.
+        let buffered_direction = self.buffered_direction.filter(|direction| *direction != field.snake.direction);
+        let arrows = iter::once((field.snake.direction, ARROW_COLOR)).chain(buffered_direction.map(|direction| (direction, BUFFERED_ARROW_COLOR)));
 
-                field.snake.try_change_direction(snake_direction);
-            }
+        //with --smooth the arrows ride along with the sliding head
+        let head_origin = match &self.motion {
+            Some(motion) => motion.origin(0, self.step_progress, cell_width, cell_height, cell_gap),
+            None => None,
+        };
+        let head_origin = head_origin.unwrap_or_else(|| cell_origin(snake_head, cell_width, cell_height, cell_gap));
 
-            None => {}
+        for (direction, mut color) in arrows {
+            color.w = snake_color.w;
+            self.shader_program.set_uniform_vec4(&self.color_uniform, &color);
+
+            let (dx, dy) = direction_arrow_offset(direction);
+            let center = Vector3f::new(
+                head_origin.0 + cell_width / 2.0 + dx * cell_width * (1.0 - ARROW_SIZE) / 2.0,
+                head_origin.1 + cell_height / 2.0 + dy * cell_height * (1.0 - ARROW_SIZE) / 2.0,
+                0.0,
+            );
+            let arrow_projection = projection
+                .clone()
+                .translate(&center)
+                .scale(&Vector3f::new(ARROW_SIZE, ARROW_SIZE, 1.0))
+                .translate(&Vector3f::new(-cell_width / 2.0, -cell_height / 2.0, 0.0));
+
+            self.shader_program.set_uniform_mat4(&self.projection_uniform, &arrow_projection);
+            self.quads[0].draw_elements(QUAD_INDEX_COUNT);
         }
 
-        field.handle_snake_fruit_collision();
-        field.push_snake();
-        if field.check_snake_collision() || field.check_win() {
-            break;
-        } else {
-            renderer.render(&field);
+        self.shader_program.set_uniform_mat4(&self.projection_uniform, projection);
+    }
+}
+
+const ARROW_SIZE: f32 = 0.3; //of a cell
+const TAIL_TIP_SHADE: f32 = 0.35; //brightness of the tail end relative to the body with --gradient
+const GAME_OVER_SPIN_SPEED: f32 = std::f32::consts::PI / 2.0; //radians per second
+
+//unit step towards the edge a direction points at, in field space (y down)
+fn direction_arrow_offset(direction: SnakeDirection) -> (f32, f32) {
+    let delta = direction.delta();
+    (delta.x as f32, delta.y as f32)
+}
+
+const FRUIT_PULSE_PERIOD: f32 = 1.2; //seconds
+const FRUIT_PULSE_MIN: f32 = 0.6; //darkest brightness factor
+const GOLDEN_FRUIT_COLOR: Vector4f = Vector4f::new(1.0, 0.8, 0.15, 1.0);
+const GOLDEN_FRUIT_PULSE_SPEEDUP: f32 = 3.0; //pulses faster, it won't be around for long
+
+//scales the RGB of `color` between FRUIT_PULSE_MIN and full brightness, keeping it opaque
+fn pulsed_color(color: &Vector4f, seconds: f32) -> Vector4f {
+    let wave = 0.5 + 0.5 * (seconds * std::f32::consts::TAU / FRUIT_PULSE_PERIOD).sin();
+    let mut pulsed = color * (FRUIT_PULSE_MIN + (1.0 - FRUIT_PULSE_MIN) * wave);
+    pulsed.w = 1.0;
+    return pulsed;
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+enum RenderPass {
+    Opaque,
+    Transparent,
+}
+
+//opaque cells first with blending off, then the translucent ones over them
+const RENDER_PASSES: [RenderPass; 2] = [RenderPass::Opaque, RenderPass::Transparent];
+
+impl RenderPass {
+    fn blends(self) -> bool {
+        return self == RenderPass::Transparent;
+    }
+}
+
+//what a cell drawn over the board shows, which decides its color
+#[derive(PartialEq, Clone, Copy, Debug)]
+enum CellContent {
+    Obstacle,
+    Fruit(FruitKind),
+    SnakeHead,
+    SnakeTail(usize), //position counted from the head, 1 is the neck
+}
+
+// The cells draw_field draws over the board in `pass`, in drawing order: the obstacles, the fruit,
+// then the snake from the head to the tail end. Replaces what `cells` held before.
+fn field_cells(field: &SnakeGameField, pass: RenderPass, cells: &mut Vec<(Point, CellContent)>) {
+    cells.clear();
+
+    if pass == RenderPass::Opaque {
+        cells.extend(field.obstacles.iter().map(|obstacle| (*obstacle, CellContent::Obstacle)));
+
+        //a fruit under the head (e.g. on a fresh field) is hidden by it, don't draw the cell twice
+        if let Some(Fruit(fruit, kind)) = &field.fruit {
+            if *fruit != field.snake.head && field.in_bounds(fruit) {
+                cells.push((*fruit, CellContent::Fruit(*kind)));
+            }
         }
+    }
 
-        window.swap_buffers();
+    //an invulnerable snake is translucent, so it belongs to the transparent pass; the tail may be empty
+    if field.in_grace_period() == (pass == RenderPass::Transparent) {
+        cells.push((field.snake.head, CellContent::SnakeHead));
+        let tail = field.snake.tail.iter().rev().enumerate();
+        cells.extend(tail.map(|(index, part)| (*part, CellContent::SnakeTail(index + 1))));
+    }
+}
+
+//notes a drawn cell in debug cells mode, reporting it and returning true if it was drawn before this frame
+fn track_cell(drawn_cells: &mut Option<HashSet<Point>>, point: &Point) -> bool {
+    let drawn_cells = match drawn_cells {
+        Some(drawn_cells) => drawn_cells,
+        None => return false,
+    };
 
-        thread::sleep(Duration::from_millis(200));
+    if drawn_cells.insert(*point) {
+        return false;
     }
+
+    eprintln!("Cell ({}, {}) was drawn more than once in a frame", point.x, point.y);
+    return true;
 }
 
-//INPUT
+//None for cells off the grid instead of an index into some other cell's quad
+#[inline]
+const fn quad_index(cell: &Point, size_x: i32, size_y: i32) -> Option<usize> {
+    if cell.x < 0 || cell.x >= size_x || cell.y < 0 || cell.y >= size_y {
+        return None;
+    }
 
-#[derive(Debug)]
-enum GameKey {
-    Up,
-    Right,
-    Down,
-    Left,
-    Exit,
+    Some((cell.x + cell.y * size_x) as usize)
 }
 
-fn handle_input(key: Key, action: Action) -> Option<GameKey> {
-    if action != Action::Press {
-        return Option::None;
+#[inline]
+fn checker_shade(cell: &Point) -> usize {
+    ((cell.x + cell.y) % 2) as usize
+}
+
+fn sample_gradient(stops: &[Vector4f], t: f32) -> Vector4f {
+    if stops.len() == 1 {
+        return stops[0].clone();
     }
 
-    return match key {
-        Key::W | Key::Up => Option::Some(GameKey::Up),
-        Key::D | Key::Right => Option::Some(GameKey::Right),
-        Key::S | Key::Down => Option::Some(GameKey::Down),
-        Key::A | Key::Left => Option::Some(GameKey::Left),
-        Key::Escape => Option::Some(GameKey::Exit),
-        _ => Option::None,
-    };
+    let position = t.clamp(0.0, 1.0) * (stops.len() - 1) as f32;
+    let index = (position as usize).min(stops.len() - 2);
+
+    return stops[index].lerp(&stops[index + 1], position - index as f32);
 }
 
-struct SnakeGameRenderer {
-    square: VertexArrayObject,
-    quads: Box<[VertexArrayObject]>,
-    shader_program: ShaderProgram,
-    color_uniform: UniformLocation,
+fn score_milestone_color(score: usize, gradient: &[Vector4f]) -> Vector4f {
+    let milestones = score / SCORE_MILESTONE;
+    sample_gradient(gradient, milestones as f32 / SNAKE_BODY_GRADIENT_MILESTONES as f32)
 }
 
-impl SnakeGameRenderer {
-    fn setup() -> SnakeGameRenderer {
-        let shader_program = create_shader_program();
+#[inline]
+fn create_shader_program(vertex_src: &str, fragment_src: &str) -> Result<ShaderProgram, RendererError> {
+    let mut vertex_shader = Shader::create(ShaderType::Vertex);
+    vertex_shader.src(vertex_src)?;
+    vertex_shader.compile()?;
 
-        let vertices = gen_vertices();
-        let (vbo, ebo) = gen_buffer_objects(vertices.as_slice());
-        let (square, quads) = gen_vertex_array_objects(&vbo, &ebo);
+    let mut fragment_shader = Shader::create(ShaderType::Fragment);
+    fragment_shader.src(fragment_src)?;
+    fragment_shader.compile()?;
 
-        let color_uniform = UniformLocation::get(&shader_program, "inColor");
+    let mut shader_program = ShaderProgram::create();
+    shader_program.attach(&vertex_shader);
+    shader_program.attach(&fragment_shader);
+    shader_program.link()?;
 
-        return SnakeGameRenderer {
-            square,
-            quads,
-            shader_program,
-            color_uniform,
-        };
+    //the linked program keeps the compiled code, so shaders can be deleted when they go out of scope
+    shader_program.detach(&vertex_shader);
+    shader_program.detach(&fragment_shader);
+
+    return Ok(shader_program);
+}
+
+#[inline]
+fn field_size(size_x: i32, size_y: i32, cell_width: f32, cell_height: f32, cell_gap: f32) -> (f32, f32) {
+    let axis = |cells: i32, cell_size: f32| cells as f32 * cell_size + (cells - 1) as f32 * cell_gap;
+    (axis(size_x, cell_width), axis(size_y, cell_height))
+}
+
+//maps field-local coordinates to clip space, centering (and rotating) the field in the window
+fn field_projection(size_x: i32, size_y: i32, cell_width: f32, cell_height: f32, cell_gap: f32, rotation: f32) -> Matrix4f {
+    let (field_width, field_height) = field_size(size_x, size_y, cell_width, cell_height, cell_gap);
+    view_projection((field_width / 2.0, field_height / 2.0), 1.0, rotation)
+}
+
+//puts the field-local point `center` in the middle of the window, rotating and zooming around it
+fn view_projection(center: (f32, f32), zoom: f32, rotation: f32) -> Matrix4f {
+    let width = WINDOW_WIDTH as f32;
+    let height = WINDOW_HEIGHT as f32;
+
+    let projection = ortho2d(0.0, width, height, 0.0);
+    projection
+        .translate(&Vector3f::new(width / 2.0, height / 2.0, 0.0))
+        .rotate_z(rotation)
+        .scale(&Vector3f::new(zoom, zoom, 1.0))
+        .translate(&Vector3f::new(-center.0, -center.1, 0.0))
+}
+
+//CELL BATCH
+
+const INSTANCE_FLOATS: usize = 2 + 4; //cell origin, color
+
+// Draws queued cells with a single instanced draw call: every instance is the first cell's quad,
+// moved to its cell by a per-instance origin and filled with a per-instance color. It has its own
+// program and VAO, the cell quad and indices come from the renderer's buffers.
+struct CellBatch {
+    vao: VertexArrayObject, //goes before the instance buffer it references
+    instance_buffer: BufferObject,
+    program: ShaderProgram,
+    projection_uniform: UniformLocation,
+    capacity: usize, //instances the buffer has room for
+    instances: Vec<f32>,
+}
+
+impl CellBatch {
+    fn setup(vbo: &BufferObject, ebo: &BufferObject, capacity: usize) -> Result<CellBatch, RendererError> {
+        let program = create_shader_program(INSTANCED_VERTEX_SHADER_SRC, INSTANCED_FRAGMENT_SHADER_SRC)?;
+        let projection_uniform = UniformLocation::get(&program, "projection");
+
+        const FLOAT_SIZE: usize = mem::size_of::<GLfloat>();
+        const VEC2_SIZE: GLsizei = (2 * FLOAT_SIZE) as GLsizei;
+        const INSTANCE_SIZE: GLsizei = (INSTANCE_FLOATS * FLOAT_SIZE) as GLsizei;
+
+        let instance_buffer = BufferObject::gen();
+        let vao = VertexArrayObject::gen();
+        vao.bind();
+
+        unsafe {
+            //the first cell's quad, right after the field square (see gen_vertices)
+            vbo.bind(BufferTarget::ArrayBuffer);
+            gl::VertexAttribPointer(0, 2, gl::FLOAT, gl::FALSE, VEC2_SIZE, (4 * VEC2_SIZE) as *const _);
+            gl::EnableVertexAttribArray(0);
+
+            instance_buffer.bind(BufferTarget::ArrayBuffer);
+            gl::BufferData(
+                BufferTarget::ArrayBuffer.into_raw(),
+                (capacity * INSTANCE_SIZE as usize) as GLsizeiptr,
+                ptr::null(),
+                GlDrawType::Stream.into_raw(),
+            );
+            gl::VertexAttribPointer(1, 2, gl::FLOAT, gl::FALSE, INSTANCE_SIZE, ptr::null());
+            gl::VertexAttribPointer(2, 4, gl::FLOAT, gl::FALSE, INSTANCE_SIZE, (2 * FLOAT_SIZE) as *const _);
+            gl::EnableVertexAttribArray(1);
+            gl::EnableVertexAttribArray(2);
+            gl::VertexAttribDivisor(1, 1); //advance once per instance instead of per vertex
+            gl::VertexAttribDivisor(2, 1);
+
+            ebo.bind(BufferTarget::ElementArrayBuffer);
+        }
+
+        unbind_vao();
+        unbind_buffer_object(BufferTarget::ArrayBuffer);
+        unbind_buffer_object(BufferTarget::ElementArrayBuffer);
+
+        return Ok(CellBatch {
+            vao,
+            instance_buffer,
+            program,
+            projection_uniform,
+            capacity,
+            instances: Vec::with_capacity(capacity * INSTANCE_FLOATS),
+        });
     }
 
-    fn prepare_renderer(&self) {
-        self.shader_program.use_program();
-        set_clear_color(&Vector4f::zeroed()); //black
+    fn push(&mut self, origin: (f32, f32), color: &Vector4f) {
+        self.instances.extend([origin.0, origin.1, color.x, color.y, color.z, color.w]);
     }
 
-    fn render(&mut self, field: &SnakeGameField) {
-        fn draw_quad() {
-            unsafe {
-                gl::DrawElements(gl::TRIANGLES, 6, gl::UNSIGNED_INT, ptr::null());
-            }
+    // Uploads and draws everything queued so far and empties the queue. Returns whether it drew,
+    // the batch's program is left in use then.
+    fn flush(&mut self, projection: &Matrix4f) -> bool {
+        let count = self.instances.len() / INSTANCE_FLOATS;
+        if count == 0 {
+            return false;
         }
 
-        const fn get_quad<'a>(quads: &'a [VertexArrayObject], point: &Point) -> &'a VertexArrayObject {
-            &quads[(point.x + point.y * 10) as usize]
+        let size = (self.instances.len() * mem::size_of::<GLfloat>()) as GLsizeiptr;
+        let data = self.instances.as_ptr() as *const c_void;
+        self.instance_buffer.bind(BufferTarget::ArrayBuffer);
+
+        unsafe {
+            if count > self.capacity {
+                //only if more got queued than setup planned for
+                gl::BufferData(BufferTarget::ArrayBuffer.into_raw(), size, data, GlDrawType::Stream.into_raw());
+                self.capacity = count;
+            } else {
+                gl::BufferSubData(BufferTarget::ArrayBuffer.into_raw(), 0, size, data);
+            }
         }
 
-        let color_uniform = &self.color_uniform;
+        unbind_buffer_object(BufferTarget::ArrayBuffer);
 
-        clear_color_buffer();
+        self.program.use_program();
+        self.program.set_uniform_mat4(&self.projection_uniform, projection);
+        self.vao.draw_elements_instanced(QUAD_INDEX_COUNT, count as GLsizei);
 
-        //RENDER FIELD SQUARE
-        self.shader_program.set_uniform_vec4(color_uniform, &SQUARE_COLOR);
+        self.instances.clear();
+        return true;
+    }
+}
 
-        self.square.bind();
-        draw_quad();
+//field-local top left corner of a cell, where its quad starts
+fn cell_origin(cell: &Point, cell_width: f32, cell_height: f32, cell_gap: f32) -> (f32, f32) {
+    (cell.x as f32 * (cell_width + cell_gap), cell.y as f32 * (cell_height + cell_gap))
+}
 
-        //RENDER FRUIT
-        let fruit = &field.fruit.0;
-        let snake_head = &field.snake.head;
+//SNAKE MOTION
 
-        if *snake_head != *fruit {
-            self.shader_program.set_uniform_vec4(color_uniform, &FRUIT_COLOR);
+// The snake's cells at the last two cell steps, head first, for --smooth. Every part slides from
+// its previous cell into its current one while the next step builds up, so the picture runs up to
+// one step behind the game. A part that wrapped around the board snaps instead of sliding across it.
+struct SnakeMotion {
+    ticks: u64, //field.ticks when `current` was taken
+    previous: Vec<Point>,
+    current: Vec<Point>,
+    wrapped: Vec<bool>, //per part, whether its latest move went across the edge
+}
+
+impl SnakeMotion {
+    fn new() -> SnakeMotion {
+        SnakeMotion {
+            ticks: 0,
+            previous: Vec::new(),
+            current: Vec::new(),
+            wrapped: Vec::new(),
+        }
+    }
 
-            let fruit_quad = get_quad(&self.quads, fruit);
-            fruit_quad.bind();
-            draw_quad();
+    //call once per frame; anything but a single step forward (a restart, say) shows the snake at rest
+    fn update(&mut self, field: &SnakeGameField) {
+        if field.ticks == self.ticks && !self.current.is_empty() {
+            return;
         }
 
-        //RENDER SNAKE
-        self.shader_program.set_uniform_vec4(color_uniform, &SNAKE_PART_COLOR);
+        let parts: Vec<Point> = iter::once(field.snake.head).chain(field.snake.tail.iter().rev().copied()).collect();
+        let moved_one_step = field.ticks == self.ticks + 1;
 
-        let snake_head_quad = get_quad(&self.quads, snake_head);
-        snake_head_quad.bind();
-        draw_quad();
+        if moved_one_step {
+            //every part takes the cell of the one ahead of it, so it repeats that one's move from the step before
+            let wrapped = iter::once(field.wrapped_last_move()).chain(self.wrapped.iter().copied());
+            self.wrapped = wrapped.take(parts.len()).collect();
+            self.previous = mem::replace(&mut self.current, parts);
+        } else {
+            self.wrapped = vec![false; parts.len()];
+            self.previous = parts.clone();
+            self.current = parts;
+        }
+        self.ticks = field.ticks;
+    }
+
+    //where the part `index` (0 is the head) is drawn, None if it's unknown
+    fn origin(&self, index: usize, progress: f32, cell_width: f32, cell_height: f32, cell_gap: f32) -> Option<(f32, f32)> {
+        let to = *self.current.get(index)?;
+        let from = self.previous.get(index).copied().unwrap_or(to); //a segment that just grew appears in place
 
-        for tail_part in field.snake.tail.iter() {
-            let part_quad = get_quad(&self.quads, tail_part);
-            part_quad.bind();
-            draw_quad();
+        let (to_x, to_y) = cell_origin(&to, cell_width, cell_height, cell_gap);
+        if self.wrapped.get(index) == Some(&true) {
+            return Some((to_x, to_y));
         }
+
+        let (from_x, from_y) = cell_origin(&from, cell_width, cell_height, cell_gap);
+        return Some((from_x + (to_x - from_x) * progress, from_y + (to_y - from_y) * progress));
     }
 }
 
-#[inline]
-fn create_shader_program() -> ShaderProgram {
-    let mut vertex_shader = Shader::create(ShaderType::Vertex);
-    vertex_shader.src(VERTEX_SHADER_SRC).unwrap();
-    vertex_shader.compile().unwrap();
+//CAMERA
 
-    let mut fragment_shader = Shader::create(ShaderType::Fragment);
-    fragment_shader.src(FRAGMENT_SHADER_SRC).unwrap();
-    fragment_shader.compile().unwrap();
+const CAMERA_SMOOTHING: f32 = 0.35; //share of the remaining distance to the head covered each frame
 
-    let mut shader_program = ShaderProgram::create();
-    shader_program.attach(&vertex_shader);
-    shader_program.attach(&fragment_shader);
-    shader_program.link().unwrap();
+// Zoomed-in view following the snake head. With wrapping the field is tiled around itself, so the
+// view can follow the head anywhere; without it the view is clamped to the field edges.
+struct Camera {
+    zoom: f32,
+    center: Option<(f32, f32)>, //field-local, none until the first frame
+}
+
+impl Camera {
+    fn new(zoom: f32) -> Camera {
+        Camera { zoom, center: None }
+    }
+
+    fn visible_size(&self) -> (f32, f32) {
+        (WINDOW_WIDTH as f32 / self.zoom, WINDOW_HEIGHT as f32 / self.zoom)
+    }
 
-    return shader_program;
+    // Moves the center toward the head. `period` is the distance between tiled copies of the field
+    // along a wrapping axis; the center is kept within the first copy.
+    fn follow(&mut self, head: (f32, f32), field_size: (f32, f32), period: (f32, f32), wrap: (bool, bool)) -> (f32, f32) {
+        let target = camera_target(head, field_size, self.visible_size(), wrap);
+
+        let center = match self.center {
+            None => target,
+            Some(center) => {
+                let follow_axis = |center: f32, target: f32, period: f32, wrap: bool| {
+                    //in wrap mode the head jumping across the seam must not drag the camera across the field
+                    let center = if wrap {
+                        center + period * ((target - center) / period).round()
+                    } else {
+                        center
+                    };
+                    let center = center + (target - center) * CAMERA_SMOOTHING;
+                    if wrap {
+                        center.rem_euclid(period)
+                    } else {
+                        center
+                    }
+                };
+
+                (
+                    follow_axis(center.0, target.0, period.0, wrap.0),
+                    follow_axis(center.1, target.1, period.1, wrap.1),
+                )
+            }
+        };
+
+        self.center = Some(center);
+        return center;
+    }
 }
 
-#[inline]
-fn gen_vertices() -> Vec<f32> {
-    // Vec<f32> - field square, field quads (for snake parts and fruit)
+//where the camera wants to be for a head at `head`, everything in field-local coordinates
+fn camera_target(head: (f32, f32), field_size: (f32, f32), visible_size: (f32, f32), wrap: (bool, bool)) -> (f32, f32) {
+    //a wrapping axis has no edge to stop at
+    let target_axis = |head: f32, field: f32, visible: f32, wrap: bool| {
+        if wrap {
+            head
+        } else if visible >= field {
+            field / 2.0
+        } else {
+            head.clamp(visible / 2.0, field - visible / 2.0)
+        }
+    };
 
-    // Vertices:
-    //                \/ OFFSET
-    // B1---------C1      B2---------C2
-    // |           |      |           |
-    // |           |      |           |
-    // |           |      |           |
-    // A1---------D1      A2---------D2
-    //    ^^^^ OBJECT_SIZE
+    return (
+        target_axis(head.0, field_size.0, visible_size.0, wrap.0),
+        target_axis(head.1, field_size.1, visible_size.1, wrap.1),
+    );
+}
+
+const MINIMAP_SIZE: f32 = 120.0;
+const MINIMAP_MARGIN: f32 = 10.0;
 
+//shrinks the whole field into the top right corner of the window, ignoring the view rotation
+fn minimap_projection(size_x: i32, size_y: i32, cell_width: f32, cell_height: f32, cell_gap: f32) -> Matrix4f {
     let width = WINDOW_WIDTH as f32;
     let height = WINDOW_HEIGHT as f32;
 
-    let mut vertices = Vec::<Vector4f>::with_capacity(10 * 10 * 4);
+    let (field_width, field_height) = field_size(size_x, size_y, cell_width, cell_height, cell_gap);
+    let scale = MINIMAP_SIZE / field_width.max(field_height);
+
+    let projection = ortho2d(0.0, width, height, 0.0);
+    projection
+        .translate(&Vector3f::new(width - MINIMAP_MARGIN - field_width * scale, MINIMAP_MARGIN, 0.0))
+        .scale(&Vector3f::new(scale, scale, 1.0))
+}
 
-    const OFFSET: f32 = 5.0;
-    const OBJECT_SIZE: f32 = 60.0;
+#[inline]
+fn gen_vertices(size_x: i32, size_y: i32, cell_width: f32, cell_height: f32, cell_gap: f32) -> Vec<f32> {
+    // Vec<f32> - field square, field quads (for snake parts and fruit)
 
-    let field_size = OBJECT_SIZE * 10.0 + OFFSET * 9.0;
+    // Vertices (field-local, the projection is applied in the vertex shader):
+    //                \/ cell_gap
+    // B1---------C1      B2---------C2
+    // |           |      |           |
+    // |           |      | cell_height
+    // |           |      |           |
+    // A1---------D1      A2---------D2
+    //    ^^^^ cell_width
 
-    let matrix = {
-        let field_center = field_size / 2.0;
+    let mut vertices = Vec::<Vector4f>::with_capacity((size_x * size_y + 1) as usize * 4);
 
-        let projection = ortho2d(0.0, width, height, 0.0);
-        projection.translate(&Vector3f::new(width / 2.0 - field_center, height / 2.0 - field_center, 0.0))
-    };
+    let (field_width, field_height) = field_size(size_x, size_y, cell_width, cell_height, cell_gap);
 
     //square vertices
     vertices.extend([
         Vector4f::new(0.0, 0.0, 0.0, 1.0),
-        Vector4f::new(field_size, 0.0, 0.0, 1.0),
-        Vector4f::new(0.0, field_size, 0.0, 1.0),
-        Vector4f::new(field_size, field_size, 0.0, 1.0),
+        Vector4f::new(field_width, 0.0, 0.0, 1.0),
+        Vector4f::new(0.0, field_height, 0.0, 1.0),
+        Vector4f::new(field_width, field_height, 0.0, 1.0),
     ]);
 
-    for j in (0..10).map(|x| x as f32) {
-        for i in (0..10).map(|x| x as f32) {
+    for j in (0..size_y).map(|x| x as f32) {
+        for i in (0..size_x).map(|x| x as f32) {
             let b_vertex = {
-                let start = OBJECT_SIZE + OFFSET;
-                let x = i * start;
-                let y = j * start;
+                let x = i * (cell_width + cell_gap);
+                let y = j * (cell_height + cell_gap);
                 Vector4f::new(x, y, 0.0, 1.0)
             };
 
             let c_vertex = {
-                let x = b_vertex.x + OBJECT_SIZE;
+                let x = b_vertex.x + cell_width;
                 let y = b_vertex.y;
                 Vector4f::new(x, y, 0.0, 1.0)
             };
 
             let a_vertex = {
                 let x = b_vertex.x;
-                let y = b_vertex.y + OBJECT_SIZE;
+                let y = b_vertex.y + cell_height;
                 Vector4f::new(x, y, 0.0, 1.0)
             };
 
@@ -287,15 +2436,47 @@ fn gen_vertices() -> Vec<f32> {
         }
     }
 
-    vertices
-        .into_iter()
-        .map(|vec| vec * &matrix)
-        .flat_map(|vec| [vec.x, vec.y])
-        .collect::<Vec<f32>>()
+    vertices.into_iter().flat_map(|vec| [vec.x, vec.y]).collect::<Vec<f32>>()
+}
+
+// Indices into the B, C, A, D vertices of a quad (see gen_vertices).
+// Both triangles (B-C-A and A-C-D) share the same winding.
+const QUAD_INDICES: [u32; 6] = [
+    0, 1, 2, //
+    2, 1, 3, //
+];
+const QUAD_INDEX_COUNT: GLsizei = QUAD_INDICES.len() as GLsizei;
+
+//doubled signed area of the triangle, positive for counter-clockwise winding
+#[inline]
+fn triangle_signed_area(a: (f32, f32), b: (f32, f32), c: (f32, f32)) -> f32 {
+    (b.0 - a.0) * (c.1 - a.1) - (c.0 - a.0) * (b.1 - a.1)
+}
+
+fn quad_winding_is_consistent(quad_vertices: &[f32], indices: &[u32]) -> bool {
+    let vertex = |index: u32| {
+        let index = index as usize * 2;
+        (quad_vertices[index], quad_vertices[index + 1])
+    };
+
+    let areas = indices
+        .chunks(3)
+        .map(|triangle| triangle_signed_area(vertex(triangle[0]), vertex(triangle[1]), vertex(triangle[2])))
+        .collect::<Vec<f32>>();
+
+    let non_degenerate = areas.iter().all(|area| *area != 0.0);
+    let same_winding = areas.iter().all(|area| area.is_sign_positive() == areas[0].is_sign_positive());
+
+    return non_degenerate && same_winding;
 }
 
 #[inline]
 fn gen_buffer_objects(vertices: &[f32]) -> (BufferObject, BufferObject) {
+    debug_assert!(
+        vertices.chunks(4 * 2).all(|quad| quad_winding_is_consistent(quad, &QUAD_INDICES)),
+        "quad indices don't match the vertex order produced by gen_vertices"
+    );
+
     let vbo = BufferObject::gen();
     vbo.bind(BufferTarget::ArrayBuffer);
 
@@ -308,10 +2489,7 @@ fn gen_buffer_objects(vertices: &[f32]) -> (BufferObject, BufferObject) {
         );
     }
 
-    let indices: [u32; 6] = [
-        0, 1, 2, //
-        1, 2, 3, //
-    ];
+    let indices = QUAD_INDICES;
 
     let ebo = BufferObject::gen();
     ebo.bind(BufferTarget::ElementArrayBuffer);
@@ -332,7 +2510,7 @@ fn gen_buffer_objects(vertices: &[f32]) -> (BufferObject, BufferObject) {
 }
 
 #[inline]
-fn gen_vertex_array_objects(vbo: &BufferObject, ebo: &BufferObject) -> (VertexArrayObject, Box<[VertexArrayObject]>) {
+fn gen_vertex_array_objects(vbo: &BufferObject, ebo: &BufferObject, quad_count: usize) -> (VertexArrayObject, Box<[VertexArrayObject]>) {
     vbo.bind(BufferTarget::ArrayBuffer);
 
     const VEC2_SIZE: GLsizei = (2 * mem::size_of::<GLfloat>()) as GLsizei;
@@ -359,9 +2537,9 @@ fn gen_vertex_array_objects(vbo: &BufferObject, ebo: &BufferObject) -> (VertexAr
     };
 
     let field_quads = {
-        let mut quads = Vec::<VertexArrayObject>::new();
+        let mut quads = Vec::<VertexArrayObject>::with_capacity(quad_count);
 
-        for i in 0..(10 * 10) {
+        for i in 0..quad_count as GLsizei {
             let vao = VertexArrayObject::gen();
             vao.bind();
 
@@ -386,208 +2564,383 @@ fn gen_vertex_array_objects(vbo: &BufferObject, ebo: &BufferObject) -> (VertexAr
     (field_square, field_quads.into_boxed_slice())
 }
 
-//GAME
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-#[derive(Eq, PartialEq, Clone)]
-struct Point {
-    x: i32,
-    y: i32,
-}
+    #[test]
+    fn slow_frame_catches_up_on_every_due_tick() {
+        let mut clock = GameClock::new(Duration::from_millis(40));
 
-impl Point {
-    const fn new(x: i32, y: i32) -> Point {
-        Point { x, y }
+        assert_eq!(clock.advance(Duration::from_millis(30)), 0);
+        assert_eq!(clock.advance(Duration::from_millis(100)), 3);
+        assert_eq!(clock.accumulator, Duration::from_millis(10));
     }
 
-    const fn origin() -> Point {
-        Point::new(0, 0)
+    #[test]
+    fn stalled_frame_only_counts_up_to_the_max_delta() {
+        let mut clock = GameClock::new(Duration::from_millis(100));
+
+        //250 ms worth of a 10 s stall: two ticks and half of the next one
+        assert_eq!(clock.advance(Duration::from_secs(10)), 2);
+        assert_eq!(clock.accumulator, MAX_FRAME_DELTA - Duration::from_millis(200));
+        assert!((clock.progress() - 0.5).abs() < 1e-6);
     }
-}
 
-impl Add for Point {
-    type Output = Point;
+    #[test]
+    fn same_direction_inputs_take_no_queue_slots() {
+        let mut input_queue = InputQueue::new();
+
+        for _ in 0..5 {
+            input_queue.push(SnakeDirection::Right, SnakeDirection::Right);
+        }
+        assert_eq!(input_queue.peek(), None);
 
-    fn add(self, rhs: Self) -> Self::Output {
-        Point {
-            x: self.x + rhs.x,
-            y: self.y + rhs.y,
+        for _ in 0..5 {
+            input_queue.push(SnakeDirection::Up, SnakeDirection::Right);
         }
+        input_queue.push(SnakeDirection::Left, SnakeDirection::Right);
+
+        assert_eq!(input_queue.pop(), Some(SnakeDirection::Up));
+        assert_eq!(input_queue.pop(), Some(SnakeDirection::Left));
+        assert_eq!(input_queue.pop(), None);
     }
-}
 
-impl Neg for Point {
-    type Output = Point;
+    #[test]
+    fn queued_up_then_left_turns_over_two_ticks() {
+        let mut field = SnakeGameField::create(10, 10, 1, Box::new(RandomFruitPlacer));
+        field.fruit = None;
+        let mut input_queue = InputQueue::new();
+
+        //the third press doesn't fit the queue
+        input_queue.push(SnakeDirection::Up, field.snake.direction);
+        input_queue.push(SnakeDirection::Left, field.snake.direction);
+        input_queue.push(SnakeDirection::Down, field.snake.direction);
 
-    fn neg(self) -> Self::Output {
-        Point { x: -self.x, y: -self.y }
+        tick_game(&mut field, &mut input_queue, InputTiming::StartOfTick, None);
+        assert_eq!(field.snake.head, Point::new(5, 4));
+
+        tick_game(&mut field, &mut input_queue, InputTiming::StartOfTick, None);
+        assert_eq!(field.snake.head, Point::new(4, 4));
+        assert_eq!(input_queue.peek(), None);
     }
-}
 
-impl Sub for Point {
-    type Output = Point;
+    #[test]
+    fn high_score_file_round_trips() {
+        assert_eq!(format_high_score(42), "42\n");
+        assert_eq!(parse_high_score(&format_high_score(42)), Some(42));
+        assert_eq!(parse_high_score("  7 \r\n"), Some(7));
+        assert_eq!(parse_high_score(""), None);
+        assert_eq!(parse_high_score("-3"), None);
+        assert_eq!(parse_high_score("lots"), None);
+    }
 
-    fn sub(self, rhs: Self) -> Self::Output {
-        self + (-rhs)
+    #[test]
+    fn key_bindings_resolve_defaults_and_rebinds() {
+        let mut key_bindings = KeyBindings::new();
+        assert_eq!(key_bindings.resolve(Key::W), Some(GameKey::Up));
+        assert_eq!(key_bindings.resolve(Key::Up), Some(GameKey::Up));
+        assert_eq!(key_bindings.resolve(Key::Escape), Some(GameKey::Exit));
+        assert_eq!(key_bindings.resolve(Key::Q), None);
+
+        //rebinding replaces all keys of the action and takes the new key from its old action
+        key_bindings.bind(GameKey::Up, &[Key::I, Key::D]);
+        assert_eq!(key_bindings.resolve(Key::I), Some(GameKey::Up));
+        assert_eq!(key_bindings.resolve(Key::D), Some(GameKey::Up));
+        assert_eq!(key_bindings.resolve(Key::W), None);
+        assert_eq!(key_bindings.resolve(Key::Right), Some(GameKey::Right));
     }
-}
 
-#[derive(Eq, PartialEq, Debug)]
-enum SnakeDirection {
-    Up,
-    Right,
-    Down,
-    Left,
-}
+    #[test]
+    fn snake_color_steps_through_the_gradient_per_milestone() {
+        let black = Vector4f::new(0.0, 0.0, 0.0, 1.0);
+        let white = Vector4f::new(1.0, 1.0, 1.0, 1.0);
+        let gradient = [black.clone(), white.clone()];
 
-struct Snake {
-    head: Point,
-    tail: VecDeque<Point>,
-    direction: SnakeDirection,
-    ate_fruit: bool,
-}
+        assert_eq!(score_milestone_color(0, &gradient), black);
+        assert_eq!(score_milestone_color(SCORE_MILESTONE - 1, &gradient), black);
+        assert_eq!(score_milestone_color(SCORE_MILESTONE * SNAKE_BODY_GRADIENT_MILESTONES, &gradient), white);
+        assert_eq!(score_milestone_color(SCORE_MILESTONE * 1000, &gradient), white);
 
-impl Snake {
-    fn try_change_direction(&mut self, direction: SnakeDirection) {
-        match self.direction {
-            SnakeDirection::Up if direction == SnakeDirection::Down => return,
-            SnakeDirection::Right if direction == SnakeDirection::Left => return,
-            SnakeDirection::Down if direction == SnakeDirection::Up => return,
-            SnakeDirection::Left if direction == SnakeDirection::Right => return,
+        //every milestone covers the same share of the way
+        let color = score_milestone_color(SCORE_MILESTONE * 3 + 5, &gradient);
+        assert!((color.x - 3.0 / SNAKE_BODY_GRADIENT_MILESTONES as f32).abs() < 1e-6, "got {:?}", color);
+    }
 
-            _ => {
-                self.direction = direction;
-            }
-        }
+    #[test]
+    fn quad_indices_match_generated_vertices() {
+        let vertices = gen_vertices(4, 3, 10.0, 8.0, 2.0);
+        assert_eq!(vertices.len(), (4 * 3 + 1) * 4 * 2);
+        assert!(vertices.chunks(4 * 2).all(|quad| quad_winding_is_consistent(quad, &QUAD_INDICES)));
     }
 
-    #[inline]
-    fn ate_fruit(&mut self) -> bool {
-        let ate_fruit = self.ate_fruit;
-        self.ate_fruit = false;
-        return ate_fruit;
+    #[test]
+    fn gen_vertices_lays_out_40_by_80_cells() {
+        let vertices = gen_vertices(3, 2, 40.0, 80.0, 5.0);
+        let quad = |index: usize| vertices[index * 8..index * 8 + 8].to_vec();
+
+        //the board square first, then one B, C, A, D quad per cell, row by row
+        assert_eq!(quad(0), [0.0, 0.0, 130.0, 0.0, 0.0, 165.0, 130.0, 165.0]);
+        assert_eq!(quad(1), [0.0, 0.0, 40.0, 0.0, 0.0, 80.0, 40.0, 80.0]);
+        assert_eq!(quad(1 + 4), [45.0, 85.0, 85.0, 85.0, 45.0, 165.0, 85.0, 165.0]);
+        assert_eq!(vertices.len(), (1 + 6) * 8);
     }
-}
 
-struct Fruit(Point);
+    #[test]
+    fn flipped_or_degenerate_triangles_are_inconsistent() {
+        let quad = &gen_vertices(1, 1, 10.0, 10.0, 0.0)[4 * 2..];
 
-impl Fruit {
-    fn random_from_field(field: &SnakeGameField) -> Fruit {
-        let snake = &field.snake;
-        'generation_loop: loop {
-            let fruit = Fruit::random((0, field.size_x), (0, field.size_y));
+        assert!(!quad_winding_is_consistent(quad, &[0, 1, 2, 2, 3, 1]));
+        assert!(!quad_winding_is_consistent(quad, &[0, 1, 2, 1, 1, 3]));
+        assert!(!quad_winding_is_consistent(&gen_vertices(1, 1, 0.0, 10.0, 0.0)[4 * 2..], &QUAD_INDICES));
+    }
 
-            if snake.head == fruit.0 {
-                continue;
-            }
+    #[test]
+    fn ppm_flips_rows_and_drops_alpha() {
+        //bottom row first: red, green; then the top row: blue, white
+        let pixels = [255, 0, 0, 255, 0, 255, 0, 255, 0, 0, 255, 128, 255, 255, 255, 0];
+        let mut expected = b"P6\n2 2\n255\n".to_vec();
+        expected.extend([0, 0, 255, 255, 255, 255, 255, 0, 0, 0, 255, 0]);
 
-            for tail_part in &snake.tail {
-                if *tail_part == fruit.0 {
-                    continue 'generation_loop;
-                }
-            }
+        assert_eq!(encode_ppm((2, 2), &pixels), expected);
+    }
+
+    #[test]
+    fn bench_game_plays_the_same_round_every_time() {
+        let first = bench_game();
+        assert_eq!(bench_game(), first);
+        assert!(first.ticks > 0 && first.score > 0, "{:?}", first);
+    }
 
-            return fruit;
+    #[test]
+    fn input_timing_decides_which_move_a_queued_turn_steers() {
+        for (input_timing, head) in [(InputTiming::StartOfTick, Point::new(5, 6)), (InputTiming::EndOfTick, Point::new(6, 5))] {
+            let mut field = SnakeGameField::create(10, 10, 1, Box::new(RandomFruitPlacer));
+            field.fruit = None;
+            let mut input_queue = InputQueue::new();
+            input_queue.push(SnakeDirection::Down, field.snake.direction);
+
+            assert_eq!(
+                tick_game(&mut field, &mut input_queue, input_timing, None),
+                StepOutcome::Continued { ate: false }
+            );
+            assert_eq!(field.snake.head, head, "{:?}", input_timing);
+            assert_eq!(field.snake.direction, SnakeDirection::Down, "{:?}", input_timing);
         }
     }
 
-    fn random(x_bounds: (i32, i32), y_bounds: (i32, i32)) -> Fruit {
-        let mut random = rand::thread_rng();
-        let x = random.gen_range((x_bounds.0)..(x_bounds.1));
-        let y = random.gen_range((y_bounds.0)..(y_bounds.1));
-        return Fruit(Point::new(x, y));
+    #[test]
+    fn window_creation_falls_back_from_core_to_compatibility_to_any_profile() {
+        let mut attempts = Vec::new();
+        let created = create_window_with_fallback(|config| {
+            attempts.push((config.major, config.minor, config.profile_name()));
+            Some(attempts.len()).filter(|attempt| *attempt == 2)
+        });
+        assert_eq!(created, Some(2));
+        assert_eq!(attempts, [(3, 3, "core"), (3, 3, "compatibility")]);
+
+        let mut attempts = Vec::new();
+        let created = create_window_with_fallback(|config| {
+            attempts.push((config.major, config.minor, config.profile_name()));
+            None::<()>
+        });
+        assert_eq!(created, None);
+        assert_eq!(attempts, [(3, 3, "core"), (3, 3, "compatibility"), (3, 3, "default")]);
+
+        //the first config that works is kept, nothing else is tried
+        let mut attempts = 0;
+        assert_eq!(
+            create_window_with_fallback(|_| {
+                attempts += 1;
+                Some(())
+            }),
+            Some(())
+        );
+        assert_eq!(attempts, 1);
     }
-}
 
-struct SnakeGameField {
-    size_x: i32,
-    size_y: i32,
-    snake: Snake,
-    fruit: Fruit,
-}
+    #[test]
+    fn camera_target_stops_at_the_board_corner() {
+        let (field, visible) = ((1000.0, 800.0), (400.0, 400.0));
 
-impl SnakeGameField {
-    fn create() -> SnakeGameField {
-        let size_x = 10;
-        let size_y = 10;
+        assert_eq!(camera_target((10.0, 790.0), field, visible, (false, false)), (200.0, 600.0));
+        assert_eq!(camera_target((995.0, 5.0), field, visible, (false, false)), (800.0, 200.0));
+        assert_eq!(camera_target((500.0, 300.0), field, visible, (false, false)), (500.0, 300.0));
 
-        return SnakeGameField {
-            size_x,
-            size_y,
-            snake: Snake {
-                head: Point::origin(),
-                tail: VecDeque::new(),
-                direction: SnakeDirection::Right,
-                ate_fruit: false,
-            },
-            fruit: Fruit::random((1, size_x), (1, size_y)),
-        };
+        //a wrapping axis follows the head into the corner, a board smaller than the view stays centered
+        assert_eq!(camera_target((10.0, 790.0), field, visible, (true, false)), (10.0, 600.0));
+        assert_eq!(camera_target((10.0, 790.0), field, (1200.0, 900.0), (false, false)), (500.0, 400.0));
     }
 
-    fn handle_snake_fruit_collision(&mut self) {
-        if self.snake.head == self.fruit.0 {
-            self.snake.ate_fruit = true;
-            self.fruit = Fruit::random_from_field(self);
-        }
+    //where `projection` puts the field-local `point`, in clip space
+    fn assert_projects_to(projection: &Matrix4f, point: (f32, f32), expected: (f32, f32)) {
+        let clip = projection.clone() * Vector4f::new(point.0, point.1, 0.0, 1.0);
+        assert!(
+            (clip.x - expected.0).abs() < 1e-4 && (clip.y - expected.1).abs() < 1e-4,
+            "{:?} went to ({}, {}), expected {:?}",
+            point,
+            clip.x,
+            clip.y,
+            expected
+        );
     }
 
-    fn push_snake(&mut self) {
-        let snake = &mut self.snake;
-        let head = &mut snake.head;
+    #[test]
+    fn minimap_fits_the_board_into_the_top_right_corner() {
+        //a 645 unit square board scaled into the MINIMAP_SIZE square, MINIMAP_MARGIN from both edges
+        let projection = minimap_projection(10, 10, 60.0, 60.0, 5.0);
+        assert_projects_to(&projection, (0.0, 0.0), (0.675, 0.975));
+        assert_projects_to(&projection, (645.0, 645.0), (0.975, 0.675));
+
+        //a wide board keeps its aspect ratio, the shorter side ends up shorter
+        let projection = minimap_projection(20, 10, 60.0, 60.0, 5.0);
+        assert_projects_to(&projection, (0.0, 0.0), (0.675, 0.975));
+        assert_projects_to(&projection, (1295.0, 645.0), (0.975, 0.975 - 645.0 / 1295.0 * 0.3));
+    }
 
-        let old_head = head.clone();
-        match snake.direction {
-            SnakeDirection::Up => {
-                head.y -= 1;
-                if head.y == -1 {
-                    head.y = self.size_y - 1;
-                }
-            }
+    #[test]
+    fn field_projection_centers_the_board_in_the_window() {
+        //645 units square on an 800 pixel window leaves 77.5 pixels around it
+        let projection = field_projection(10, 10, 60.0, 60.0, 5.0, 0.0);
+        assert_projects_to(&projection, (0.0, 0.0), (-0.80625, 0.80625));
+        assert_projects_to(&projection, (645.0, 0.0), (0.80625, 0.80625));
+        assert_projects_to(&projection, (0.0, 645.0), (-0.80625, -0.80625));
+        assert_projects_to(&projection, (322.5, 322.5), (0.0, 0.0));
+    }
 
-            SnakeDirection::Right => {
-                head.x += 1;
-                if head.x == self.size_x {
-                    head.x = 0;
-                }
-            }
+    #[test]
+    fn rotated_view_turns_the_corner_cell_clockwise() {
+        //the center of cell (0, 0), 292.5 units up and left of the board center
+        let corner_cell = (30.0, 30.0);
 
-            SnakeDirection::Down => {
-                head.y += 1;
-                if head.y == self.size_y {
-                    head.y = 0;
-                }
-            }
+        let quarter_turn = field_projection(10, 10, 60.0, 60.0, 5.0, 90f32.to_radians());
+        assert_projects_to(&quarter_turn, corner_cell, (0.73125, 0.73125));
 
-            SnakeDirection::Left => {
-                head.x -= 1;
-                if head.x == -1 {
-                    head.x = self.size_x - 1;
-                }
-            }
+        let half_turn = field_projection(10, 10, 60.0, 60.0, 5.0, 180f32.to_radians());
+        assert_projects_to(&half_turn, corner_cell, (0.73125, -0.73125));
+    }
+
+    #[test]
+    fn checker_shade_alternates_between_neighbouring_cells() {
+        let shades = [(0, 0, 0), (1, 0, 1), (0, 1, 1), (1, 1, 0), (4, 7, 1), (9, 9, 0)];
+        for (x, y, shade) in shades {
+            assert_eq!(checker_shade(&Point::new(x, y)), shade, "cell ({}, {})", x, y);
+        }
+    }
+
+    #[test]
+    fn direction_arrows_point_at_the_matching_head_edge() {
+        //field space is y down, so up moves the arrow towards smaller y
+        let cases = [
+            (SnakeDirection::Up, (0.0, -1.0)),
+            (SnakeDirection::Right, (1.0, 0.0)),
+            (SnakeDirection::Down, (0.0, 1.0)),
+            (SnakeDirection::Left, (-1.0, 0.0)),
+        ];
+
+        for (direction, offset) in cases {
+            assert_eq!(direction_arrow_offset(direction), offset, "{:?}", direction);
         }
+    }
 
-        if !snake.tail.is_empty() {
-            snake.tail.push_back(old_head);
+    #[test]
+    fn quad_index_finds_every_snake_part_and_nothing_off_the_board() {
+        let parts = [Point::new(2, 1), Point::new(1, 1), Point::new(1, 0), Point::new(0, 0), Point::new(4, 2)];
+        let indices = parts.iter().map(|part| quad_index(part, 5, 3)).collect::<Vec<Option<usize>>>();
+        assert_eq!(indices, [Some(7), Some(6), Some(1), Some(0), Some(14)]);
 
-            if !snake.ate_fruit() {
-                snake.tail.pop_front();
-            }
-        } else if snake.ate_fruit() {
-            snake.tail.push_back(old_head);
+        //off any edge, even where x + y * size_x would land on some other cell
+        for cell in [Point::new(-1, 1), Point::new(5, 0), Point::new(0, -1), Point::new(0, 3)] {
+            assert_eq!(quad_index(&cell, 5, 3), None, "{:?}", cell);
         }
     }
 
-    fn check_snake_collision(&self) -> bool {
-        let head = &self.snake.head;
-        for tail_part in &self.snake.tail {
-            if *head == *tail_part {
-                return true;
+    //the cells of one frame the way draw_field tracks them in debug cells mode, returning the repeats
+    fn repeated_cells(field: &SnakeGameField) -> Vec<Point> {
+        let mut drawn_cells = Some(HashSet::new());
+        let mut cells = Vec::new();
+        let mut repeated = Vec::new();
+
+        for &pass in RENDER_PASSES.iter() {
+            field_cells(field, pass, &mut cells);
+            for (cell, _) in cells.iter().filter(|(cell, _)| field.in_bounds(cell)) {
+                if track_cell(&mut drawn_cells, cell) {
+                    repeated.push(*cell);
+                }
             }
         }
 
-        return false;
+        return repeated;
+    }
+
+    #[test]
+    fn debug_cells_reports_a_cell_drawn_twice() {
+        let mut field = SnakeGameField::create(10, 10, 1, Box::new(RandomFruitPlacer));
+        field.set_start_length(4).unwrap();
+        assert_eq!(repeated_cells(&field), []);
+
+        //turning back into itself during the grace period stacks two parts on one cell
+        field.grace_ticks = 10;
+        field.snake.direction = SnakeDirection::Down;
+        field.step(Some(SnakeDirection::Left));
+        field.step(Some(SnakeDirection::Up));
+        let mut parts = HashSet::new();
+        let stacked: Vec<Point> = iter::once(field.snake.head)
+            .chain(field.snake.tail.iter().copied())
+            .filter(|part| !parts.insert(*part))
+            .collect();
+        assert_eq!(stacked.len(), 1);
+        assert_eq!(repeated_cells(&field), stacked);
+
+        //outside debug cells mode nothing is tracked
+        assert!(!track_cell(&mut None, &field.snake.head));
     }
 
-    fn check_win(&self) -> bool {
-        return self.size_x * self.size_y == (self.snake.tail.len() + 1) as i32; //+ HEAD_LENGTH
+    #[test]
+    fn opaque_cells_draw_before_the_translucent_snake() {
+        assert_eq!(RENDER_PASSES, [RenderPass::Opaque, RenderPass::Transparent]);
+        assert!(!RenderPass::Opaque.blends());
+        assert!(RenderPass::Transparent.blends());
+
+        let mut field = SnakeGameField::create(10, 10, 1, Box::new(RandomFruitPlacer));
+        field.obstacles.insert(Point::new(0, 0));
+        field.fruit = Some(Fruit(Point::new(8, 8), FruitKind::Normal));
+        let mut cells = Vec::new();
+
+        //a vulnerable snake is opaque and goes on top of the obstacles and the fruit
+        field_cells(&field, RenderPass::Opaque, &mut cells);
+        let contents: Vec<CellContent> = cells.iter().map(|(_, content)| *content).collect();
+        assert_eq!(contents, [CellContent::Obstacle, CellContent::Fruit(FruitKind::Normal), CellContent::SnakeHead]);
+        field_cells(&field, RenderPass::Transparent, &mut cells);
+        assert_eq!(cells, []);
+
+        //an invulnerable one moves to the transparent pass, drawn after everything opaque
+        field.grace_ticks = 10;
+        field_cells(&field, RenderPass::Opaque, &mut cells);
+        let contents: Vec<CellContent> = cells.iter().map(|(_, content)| *content).collect();
+        assert_eq!(contents, [CellContent::Obstacle, CellContent::Fruit(FruitKind::Normal)]);
+        field_cells(&field, RenderPass::Transparent, &mut cells);
+        assert_eq!(cells, [(field.snake.head, CellContent::SnakeHead)]);
+    }
+
+    #[test]
+    fn a_fresh_field_draws_only_the_fruit_and_the_head_over_the_board() {
+        let mut field = SnakeGameField::create(10, 10, 1, Box::new(RandomFruitPlacer));
+        assert!(field.snake.tail.is_empty());
+        let fruit = field.fruit.as_ref().map(|fruit| fruit.0).unwrap();
+        let mut cells = Vec::new();
+
+        field_cells(&field, RenderPass::Opaque, &mut cells);
+        assert_eq!(
+            cells,
+            [(fruit, CellContent::Fruit(FruitKind::Normal)), (field.snake.head, CellContent::SnakeHead)]
+        );
+        field_cells(&field, RenderPass::Transparent, &mut cells);
+        assert_eq!(cells, []);
+
+        //a fruit under the head is covered by it, so only the head is drawn there
+        field.fruit = Some(Fruit(field.snake.head, FruitKind::Normal));
+        field_cells(&field, RenderPass::Opaque, &mut cells);
+        assert_eq!(cells, [(field.snake.head, CellContent::SnakeHead)]);
     }
 }
@@ -38,6 +38,37 @@ impl Vector3f {
     pub const fn into_array(self) -> [f32; 3] {
         [self.x, self.y, self.z]
     }
+
+    #[allow(dead_code)]
+    pub fn dot(&self, other: &Vector3f) -> f32 {
+        self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    //right-handed: x cross y is z
+    #[allow(dead_code)]
+    pub fn cross(&self, other: &Vector3f) -> Vector3f {
+        Vector3f {
+            x: self.y * other.z - self.z * other.y,
+            y: self.z * other.x - self.x * other.z,
+            z: self.x * other.y - self.y * other.x,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn length(&self) -> f32 {
+        self.dot(self).sqrt()
+    }
+
+    //None for the zero vector, which has no direction
+    #[allow(dead_code)]
+    pub fn normalized(&self) -> Option<Vector3f> {
+        let length = self.length();
+        if length == 0.0 {
+            return None;
+        }
+
+        return Some(Vector3f::new(self.x / length, self.y / length, self.z / length));
+    }
 }
 
 impl Add for Vector3f {
@@ -92,6 +123,17 @@ impl Vector4f {
     pub const fn into_array(self) -> [f32; 4] {
         [self.x, self.y, self.z, self.w]
     }
+
+    #[inline]
+    #[allow(dead_code)]
+    pub fn dot(&self, other: &Vector4f) -> f32 {
+        self.x * other.x + self.y * other.y + self.z * other.z + self.w * other.w
+    }
+
+    pub fn lerp(&self, other: &Vector4f, t: f32) -> Vector4f {
+        let t = t.clamp(0.0, 1.0);
+        (self * (1.0 - t)) + (other * t)
+    }
 }
 
 impl Add for Vector4f {
@@ -176,12 +218,119 @@ impl Matrix4f {
         )
     }
 
+    //column-major: i, j, k, l columns laid out one after another
+    pub const fn into_array(self) -> [f32; 16] {
+        let (i, j, k, l) = (self.i, self.j, self.k, self.l);
+        [
+            i.x, i.y, i.z, i.w, //
+            j.x, j.y, j.z, j.w, //
+            k.x, k.y, k.z, k.w, //
+            l.x, l.y, l.z, l.w, //
+        ]
+    }
+
+    //into_array without giving up the matrix
+    pub const fn to_array(&self) -> [f32; 16] {
+        let (i, j, k, l) = (&self.i, &self.j, &self.k, &self.l);
+        [
+            i.x, i.y, i.z, i.w, //
+            j.x, j.y, j.z, j.w, //
+            k.x, k.y, k.z, k.w, //
+            l.x, l.y, l.z, l.w, //
+        ]
+    }
+
     #[inline]
     pub fn translate(self, vec3: &Vector3f) -> Matrix4f {
         let mut translation_matrix = Matrix4f::identity();
         translation_matrix.l = Vector4f::new(vec3.x, vec3.y, vec3.z, 1.0);
         return self * translation_matrix;
     }
+
+    #[inline]
+    pub fn scale(self, vec3: &Vector3f) -> Matrix4f {
+        let mut scaling_matrix = Matrix4f::identity();
+        scaling_matrix.i.x = vec3.x;
+        scaling_matrix.j.y = vec3.y;
+        scaling_matrix.k.z = vec3.z;
+        return self * scaling_matrix;
+    }
+
+    //rotation around the Z axis; in y-down screen space a positive angle turns clockwise
+    pub fn rotation_z(radians: f32) -> Matrix4f {
+        let (sin, cos) = radians.sin_cos();
+
+        let mut rotation_matrix = Matrix4f::identity();
+        rotation_matrix.i = Vector4f::new(cos, sin, 0.0, 0.0);
+        rotation_matrix.j = Vector4f::new(-sin, cos, 0.0, 0.0);
+        return rotation_matrix;
+    }
+
+    #[inline]
+    pub fn rotate_z(self, radians: f32) -> Matrix4f {
+        self * Matrix4f::rotation_z(radians)
+    }
+
+    #[allow(dead_code)]
+    pub fn determinant(&self) -> f32 {
+        let m = self.to_array();
+        let cofactors = adjugate_first_column(&m);
+        m[0] * cofactors[0] + m[4] * cofactors[1] + m[8] * cofactors[2] + m[12] * cofactors[3]
+    }
+
+    // Full inverse through the adjugate, None for (nearly) singular matrices. Works on the flat
+    // column-major array; the same expressions invert a row-major one, since transposing commutes
+    // with inversion.
+    #[allow(dead_code)]
+    pub fn inverse(&self) -> Option<Matrix4f> {
+        let m = self.to_array();
+
+        let mut adjugate = [0.0f32; 16];
+        adjugate[..4].copy_from_slice(&adjugate_first_column(&m));
+
+        adjugate[4] = -m[4] * m[10] * m[15] + m[4] * m[11] * m[14] + m[8] * m[6] * m[15] - m[8] * m[7] * m[14] - m[12] * m[6] * m[11] + m[12] * m[7] * m[10];
+        adjugate[5] = m[0] * m[10] * m[15] - m[0] * m[11] * m[14] - m[8] * m[2] * m[15] + m[8] * m[3] * m[14] + m[12] * m[2] * m[11] - m[12] * m[3] * m[10];
+        adjugate[6] = -m[0] * m[6] * m[15] + m[0] * m[7] * m[14] + m[4] * m[2] * m[15] - m[4] * m[3] * m[14] - m[12] * m[2] * m[7] + m[12] * m[3] * m[6];
+        adjugate[7] = m[0] * m[6] * m[11] - m[0] * m[7] * m[10] - m[4] * m[2] * m[11] + m[4] * m[3] * m[10] + m[8] * m[2] * m[7] - m[8] * m[3] * m[6];
+
+        adjugate[8] = m[4] * m[9] * m[15] - m[4] * m[11] * m[13] - m[8] * m[5] * m[15] + m[8] * m[7] * m[13] + m[12] * m[5] * m[11] - m[12] * m[7] * m[9];
+        adjugate[9] = -m[0] * m[9] * m[15] + m[0] * m[11] * m[13] + m[8] * m[1] * m[15] - m[8] * m[3] * m[13] - m[12] * m[1] * m[11] + m[12] * m[3] * m[9];
+        adjugate[10] = m[0] * m[5] * m[15] - m[0] * m[7] * m[13] - m[4] * m[1] * m[15] + m[4] * m[3] * m[13] + m[12] * m[1] * m[7] - m[12] * m[3] * m[5];
+        adjugate[11] = -m[0] * m[5] * m[11] + m[0] * m[7] * m[9] + m[4] * m[1] * m[11] - m[4] * m[3] * m[9] - m[8] * m[1] * m[7] + m[8] * m[3] * m[5];
+
+        adjugate[12] = -m[4] * m[9] * m[14] + m[4] * m[10] * m[13] + m[8] * m[5] * m[14] - m[8] * m[6] * m[13] - m[12] * m[5] * m[10] + m[12] * m[6] * m[9];
+        adjugate[13] = m[0] * m[9] * m[14] - m[0] * m[10] * m[13] - m[8] * m[1] * m[14] + m[8] * m[2] * m[13] + m[12] * m[1] * m[10] - m[12] * m[2] * m[9];
+        adjugate[14] = -m[0] * m[5] * m[14] + m[0] * m[6] * m[13] + m[4] * m[1] * m[14] - m[4] * m[2] * m[13] - m[12] * m[1] * m[6] + m[12] * m[2] * m[5];
+        adjugate[15] = m[0] * m[5] * m[10] - m[0] * m[6] * m[9] - m[4] * m[1] * m[10] + m[4] * m[2] * m[9] + m[8] * m[1] * m[6] - m[8] * m[2] * m[5];
+
+        // The determinant can't be larger than the product of the column lengths (Hadamard's bound),
+        // so measuring it against that product tells a singular matrix from one with small entries,
+        // like a projection onto a large window.
+        let determinant = m[0] * adjugate[0] + m[1] * adjugate[4] + m[2] * adjugate[8] + m[3] * adjugate[12];
+        let column_lengths: f32 = m.chunks(4).map(|column| column.iter().map(|value| value * value).sum::<f32>().sqrt()).product();
+        if determinant.abs() <= SINGULAR_EPSILON * column_lengths {
+            return None;
+        }
+
+        let column = |index: usize| {
+            let a = &adjugate[index * 4..index * 4 + 4];
+            Vector4f::new(a[0], a[1], a[2], a[3]) * (1.0 / determinant)
+        };
+
+        return Some(Matrix4f::new(column(0), column(1), column(2), column(3)));
+    }
+}
+
+const SINGULAR_EPSILON: f32 = 1e-6; //relative to the largest determinant the column lengths allow
+
+//first column of the adjugate, i.e. the cofactors of the entries in the first row
+fn adjugate_first_column(m: &[f32; 16]) -> [f32; 4] {
+    [
+        m[5] * m[10] * m[15] - m[5] * m[11] * m[14] - m[9] * m[6] * m[15] + m[9] * m[7] * m[14] + m[13] * m[6] * m[11] - m[13] * m[7] * m[10],
+        -m[1] * m[10] * m[15] + m[1] * m[11] * m[14] + m[9] * m[2] * m[15] - m[9] * m[3] * m[14] - m[13] * m[2] * m[11] + m[13] * m[3] * m[10],
+        m[1] * m[6] * m[15] - m[1] * m[7] * m[14] - m[5] * m[2] * m[15] + m[5] * m[3] * m[14] + m[13] * m[2] * m[7] - m[13] * m[3] * m[6],
+        -m[1] * m[6] * m[11] + m[1] * m[7] * m[10] + m[5] * m[2] * m[11] - m[5] * m[3] * m[10] - m[9] * m[2] * m[7] + m[9] * m[3] * m[6],
+    ]
 }
 
 #[inline]
@@ -223,6 +372,12 @@ fn mul_matrix4f_by_matrix4f(this: &Matrix4f, rhs: &Matrix4f) -> Matrix4f {
     }
 }
 
+impl From<&Matrix4f> for [f32; 16] {
+    fn from(matrix: &Matrix4f) -> [f32; 16] {
+        matrix.to_array()
+    }
+}
+
 impl Mul for Matrix4f {
     type Output = Matrix4f;
 
@@ -241,3 +396,113 @@ pub fn ortho2d(left: f32, right: f32, bottom: f32, top: f32) -> Matrix4f {
     matrix.l.w = 1.0;
     return matrix;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_identity(matrix: &Matrix4f) {
+        let identity = Matrix4f::identity().to_array();
+        for (index, (value, expected)) in matrix.to_array().iter().zip(identity.iter()).enumerate() {
+            assert!((value - expected).abs() < 1e-4, "element {} is {}, expected {}", index, value, expected);
+        }
+    }
+
+    #[test]
+    fn ortho2d_times_its_inverse_is_identity() {
+        //the field projection at a small zoom, which has a determinant far below 1e-6
+        let projection = ortho2d(0.0, 8000.0, 8000.0, 0.0).scale(&Vector3f::new(0.1, 0.1, 1.0));
+        let inverse = projection.inverse().expect("the projection is invertible");
+        assert_identity(&(projection * inverse));
+    }
+
+    #[test]
+    fn translation_times_its_inverse_is_identity() {
+        let translation = Matrix4f::identity().translate(&Vector3f::new(120.0, -35.5, 2.0));
+        let inverse = translation.inverse().expect("a translation is invertible");
+        assert_identity(&(translation * inverse));
+    }
+
+    #[test]
+    fn singular_matrix_has_no_inverse() {
+        assert!(Matrix4f::zeroed().inverse().is_none());
+        assert!(Matrix4f::identity().scale(&Vector3f::new(1.0, 0.0, 1.0)).inverse().is_none());
+    }
+
+    #[test]
+    fn vector3f_dot_cross_and_normalized() {
+        let x = Vector3f::new(1.0, 0.0, 0.0);
+        let y = Vector3f::new(0.0, 1.0, 0.0);
+
+        assert_eq!(x.dot(&y), 0.0);
+        assert_eq!(Vector3f::new(1.0, 2.0, 3.0).dot(&Vector3f::new(4.0, -5.0, 6.0)), 12.0);
+        assert_eq!(x.cross(&y).into_array(), [0.0, 0.0, 1.0]);
+        assert_eq!(y.cross(&x).into_array(), [0.0, 0.0, -1.0]);
+
+        assert_eq!(Vector3f::new(3.0, 0.0, 4.0).normalized().map(Vector3f::into_array), Some([0.6, 0.0, 0.8]));
+        assert!(Vector3f::zeroed().normalized().is_none());
+    }
+
+    #[test]
+    fn to_array_is_column_major_with_the_translation_last() {
+        let translation = Matrix4f::identity().translate(&Vector3f::new(7.0, 8.0, 9.0));
+
+        #[rustfmt::skip]
+        assert_eq!(translation.to_array(), [
+            1.0, 0.0, 0.0, 0.0,
+            0.0, 1.0, 0.0, 0.0,
+            0.0, 0.0, 1.0, 0.0,
+            7.0, 8.0, 9.0, 1.0,
+        ]);
+        assert_eq!(translation.clone().into_array(), translation.to_array());
+    }
+
+    #[test]
+    fn quarter_turn_rotates_x_onto_y() {
+        let rotated = Matrix4f::rotation_z(std::f32::consts::FRAC_PI_2) * Vector4f::new(1.0, 0.0, 0.0, 1.0);
+
+        for (value, expected) in [rotated.x, rotated.y, rotated.z, rotated.w].iter().zip([0.0, 1.0, 0.0, 1.0].iter()) {
+            assert!((value - expected).abs() < 1e-6, "rotated to {:?}", rotated);
+        }
+    }
+
+    #[test]
+    fn scale_multiplies_a_point_after_the_translation() {
+        let scaled = Matrix4f::identity().scale(&Vector3f::new(2.0, 3.0, 4.0));
+        assert_eq!((scaled.i.x, scaled.j.y, scaled.k.z, scaled.l.w), (2.0, 3.0, 4.0, 1.0));
+
+        //scaling is applied to the point first, the translation moves the scaled point
+        let transform = Matrix4f::identity()
+            .translate(&Vector3f::new(10.0, 20.0, 30.0))
+            .scale(&Vector3f::new(2.0, 3.0, 4.0));
+        assert_eq!(transform * Vector4f::new(1.0, -1.0, 0.5, 1.0), Vector4f::new(12.0, 17.0, 32.0, 1.0));
+    }
+
+    #[test]
+    fn lerp_blends_between_the_ends_and_clamps() {
+        let from = Vector4f::new(0.0, 1.0, 0.2, 1.0);
+        let to = Vector4f::new(1.0, 0.0, 0.6, 0.5);
+
+        assert_eq!(from.lerp(&to, 0.0), from);
+        assert_eq!(from.lerp(&to, 1.0), to);
+        assert_eq!(from.lerp(&to, 0.5), Vector4f::new(0.5, 0.5, 0.4, 0.75));
+
+        assert_eq!(from.lerp(&to, -2.0), from);
+        assert_eq!(from.lerp(&to, 3.5), to);
+    }
+
+    #[test]
+    fn vector4f_dot_sums_the_products() {
+        assert_eq!(Vector4f::new(1.0, 2.0, 3.0, 4.0).dot(&Vector4f::new(5.0, -6.0, 7.0, 0.5)), 16.0);
+        assert_eq!(Vector4f::new(1.0, 0.0, 0.0, 0.0).dot(&Vector4f::new(0.0, 0.0, 0.0, 1.0)), 0.0);
+    }
+
+    #[test]
+    fn determinant_of_scales_and_rotations() {
+        assert_eq!(Matrix4f::identity().determinant(), 1.0);
+        assert_eq!(Matrix4f::identity().scale(&Vector3f::new(2.0, 3.0, 4.0)).determinant(), 24.0);
+        assert_eq!(Matrix4f::identity().translate(&Vector3f::new(5.0, 6.0, 7.0)).determinant(), 1.0);
+        assert_eq!(Matrix4f::identity().scale(&Vector3f::new(1.0, 0.0, 1.0)).determinant(), 0.0);
+        assert!((Matrix4f::rotation_z(0.7).determinant() - 1.0).abs() < 1e-6);
+    }
+}
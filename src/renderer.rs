@@ -1,6 +1,7 @@
-use crate::math::Vector4f;
+use crate::math::{Matrix4f, Vector3f, Vector4f};
 use gl::types::*;
 use std::ffi::{CString, NulError};
+use std::fmt;
 use std::mem::MaybeUninit;
 use std::ptr::null;
 
@@ -51,6 +52,31 @@ impl ShaderType {
     }
 }
 
+#[derive(Debug)]
+pub enum RendererError {
+    NulName(NulError),     //a shader source or name with a nul byte in it, which GL can't take
+    ShaderCompile(String), //the compiler's info log
+    ProgramLink(String),   //the linker's info log
+}
+
+impl fmt::Display for RendererError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RendererError::NulName(error) => write!(f, "shader source contains a nul byte: {}", error),
+            RendererError::ShaderCompile(log) => write!(f, "shader compilation failed:\n{}", log),
+            RendererError::ProgramLink(log) => write!(f, "shader program linking failed:\n{}", log),
+        }
+    }
+}
+
+impl std::error::Error for RendererError {}
+
+impl From<NulError> for RendererError {
+    fn from(error: NulError) -> RendererError {
+        RendererError::NulName(error)
+    }
+}
+
 pub struct Shader(GLuint);
 
 impl Shader {
@@ -59,7 +85,7 @@ impl Shader {
         return Shader(shader);
     }
 
-    pub fn src(&mut self, src: &str) -> Result<(), NulError> {
+    pub fn src(&mut self, src: &str) -> Result<(), RendererError> {
         let shader_src = CString::new(src)?;
 
         unsafe {
@@ -69,7 +95,7 @@ impl Shader {
         Ok(())
     }
 
-    pub fn compile(&mut self) -> Result<(), String> {
+    pub fn compile(&mut self) -> Result<(), RendererError> {
         unsafe {
             gl::CompileShader(self.0);
 
@@ -77,7 +103,7 @@ impl Shader {
             gl::GetShaderiv(self.0, gl::COMPILE_STATUS, &mut success);
 
             if success == (gl::FALSE as GLint) {
-                return Err(get_info_log(gl::GetShaderInfoLog, self.0));
+                return Err(RendererError::ShaderCompile(get_info_log(gl::GetShaderInfoLog, self.0)));
             }
         }
 
@@ -118,7 +144,13 @@ impl ShaderProgram {
         }
     }
 
-    pub fn link(&mut self) -> Result<(), String> {
+    pub fn detach(&mut self, shader: &Shader) {
+        unsafe {
+            gl::DetachShader(self.0, shader.0);
+        }
+    }
+
+    pub fn link(&mut self) -> Result<(), RendererError> {
         unsafe {
             gl::LinkProgram(self.0);
 
@@ -126,7 +158,7 @@ impl ShaderProgram {
             gl::GetProgramiv(self.0, gl::LINK_STATUS, success.as_mut_ptr());
 
             if success.assume_init() == gl::FALSE as GLint {
-                return Err(get_info_log(gl::GetProgramInfoLog, self.0));
+                return Err(RendererError::ProgramLink(get_info_log(gl::GetProgramInfoLog, self.0)));
             }
         }
 
@@ -139,11 +171,55 @@ impl ShaderProgram {
         }
     }
 
+    #[allow(dead_code)]
+    pub fn set_uniform_f32(&mut self, location: &UniformLocation, value: f32) {
+        unsafe {
+            gl::Uniform1f(location.0, value);
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn set_uniform_i32(&mut self, location: &UniformLocation, value: i32) {
+        unsafe {
+            gl::Uniform1i(location.0, value);
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn set_uniform_vec3(&mut self, location: &UniformLocation, value: &Vector3f) {
+        unsafe {
+            gl::Uniform3f(location.0, value.x, value.y, value.z);
+        }
+    }
+
     pub fn set_uniform_vec4(&mut self, location: &UniformLocation, value: &Vector4f) {
         unsafe {
             gl::Uniform4f(location.0, value.x, value.y, value.z, value.w);
         }
     }
+
+    //points a sampler2D uniform at the texture bound to texture unit `unit` (see Texture::bind)
+    pub fn set_uniform_sampler(&mut self, location: &UniformLocation, unit: i32) {
+        unsafe {
+            gl::Uniform1i(location.0, unit);
+        }
+    }
+
+    pub fn set_uniform_mat4(&mut self, location: &UniformLocation, value: &Matrix4f) {
+        let columns = value.to_array();
+
+        unsafe {
+            gl::UniformMatrix4fv(location.0, 1, gl::FALSE, columns.as_ptr());
+        }
+    }
+}
+
+impl Drop for ShaderProgram {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteProgram(self.0);
+        }
+    }
 }
 
 pub enum BufferTarget {
@@ -184,6 +260,14 @@ impl BufferObject {
     }
 }
 
+impl Drop for BufferObject {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteBuffers(1, &self.0);
+        }
+    }
+}
+
 pub struct VertexArrayObject(GLuint);
 
 impl VertexArrayObject {
@@ -202,18 +286,232 @@ impl VertexArrayObject {
         }
     }
 
+    // Feeds vertex attribute `location` (a position, a texture coordinate, ...) with `components`
+    // floats per vertex from the array buffer that is bound right now. `stride` and `offset` count
+    // floats: every vertex takes `stride` of them, the attribute starts `offset` into it.
+    pub fn float_attribute(&self, location: GLuint, components: GLint, stride: usize, offset: usize) {
+        const FLOAT_SIZE: usize = std::mem::size_of::<GLfloat>();
+
+        self.bind();
+
+        unsafe {
+            gl::VertexAttribPointer(
+                location,
+                components,
+                gl::FLOAT,
+                gl::FALSE,
+                (stride * FLOAT_SIZE) as GLsizei,
+                (offset * FLOAT_SIZE) as *const _,
+            );
+            gl::EnableVertexAttribArray(location);
+        }
+    }
+
+    //binds this VAO and draws `count` indices from its element buffer as triangles
+    pub fn draw_elements(&self, count: GLsizei) {
+        self.bind();
+
+        unsafe {
+            gl::DrawElements(gl::TRIANGLES, count, gl::UNSIGNED_INT, null());
+        }
+    }
+
+    //binds this VAO and draws `count` vertices straight from its array buffers as triangles
+    pub fn draw_arrays(&self, count: GLsizei) {
+        self.bind();
+
+        unsafe {
+            gl::DrawArrays(gl::TRIANGLES, 0, count);
+        }
+    }
+
+    //same as draw_elements, `instances` times over; per-instance attributes advance once per copy
+    pub fn draw_elements_instanced(&self, count: GLsizei, instances: GLsizei) {
+        self.bind();
+
+        unsafe {
+            gl::DrawElementsInstanced(gl::TRIANGLES, count, gl::UNSIGNED_INT, null(), instances);
+        }
+    }
+
     #[allow(dead_code)]
     pub const fn descriptor(&self) -> GLuint {
         self.0
     }
 }
 
+impl Drop for VertexArrayObject {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteVertexArrays(1, &self.0);
+        }
+    }
+}
+
+// A 2D RGBA texture. It's sampled with nearest filtering, so small pixel art like the font atlas stays
+// crisp when scaled up, and clamps at its edges.
+pub struct Texture(GLuint);
+
+impl Texture {
+    //`pixels` holds width * height RGBA bytes, the first row ends up at texture coordinate v = 0
+    pub fn from_rgba(width: u32, height: u32, pixels: &[u8]) -> Texture {
+        assert_eq!(pixels.len(), width as usize * height as usize * 4, "texture data doesn't match its size");
+
+        let mut texture = MaybeUninit::<GLuint>::uninit();
+
+        unsafe {
+            gl::GenTextures(1, texture.as_mut_ptr());
+            let texture = Texture(texture.assume_init());
+
+            gl::BindTexture(gl::TEXTURE_2D, texture.0);
+            gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                gl::RGBA8 as GLint,
+                width as GLsizei,
+                height as GLsizei,
+                0,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                pixels.as_ptr() as *const _,
+            );
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as GLint);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+
+            return texture;
+        }
+    }
+
+    //binds it to texture unit `unit`, which is what a sampler uniform refers to
+    pub fn bind(&self, unit: u32) {
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0 + unit);
+            gl::BindTexture(gl::TEXTURE_2D, self.0);
+        }
+    }
+}
+
+impl Drop for Texture {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteTextures(1, &self.0);
+        }
+    }
+}
+
+// An offscreen render target: a framebuffer object with an RGBA texture as its only color
+// attachment. While bound, draws land in the texture instead of the window; the viewport is left
+// to the caller.
+pub struct Framebuffer {
+    fbo: GLuint,
+    texture: GLuint,
+    width: GLsizei,
+    height: GLsizei,
+}
+
+impl Framebuffer {
+    //fails with the framebuffer status if the driver rejects the attachment
+    pub fn create(width: u32, height: u32) -> Result<Framebuffer, GLenum> {
+        let mut fbo = MaybeUninit::<GLuint>::uninit();
+        let mut texture = MaybeUninit::<GLuint>::uninit();
+
+        unsafe {
+            gl::GenTextures(1, texture.as_mut_ptr());
+            let texture = texture.assume_init();
+
+            gl::BindTexture(gl::TEXTURE_2D, texture);
+            gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                gl::RGBA8 as GLint,
+                width as GLsizei,
+                height as GLsizei,
+                0,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                null(),
+            );
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+
+            gl::GenFramebuffers(1, fbo.as_mut_ptr());
+            let framebuffer = Framebuffer {
+                fbo: fbo.assume_init(),
+                texture,
+                width: width as GLsizei,
+                height: height as GLsizei,
+            };
+
+            framebuffer.bind();
+            gl::FramebufferTexture2D(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, gl::TEXTURE_2D, texture, 0);
+            let status = gl::CheckFramebufferStatus(gl::FRAMEBUFFER);
+            unbind_framebuffer();
+
+            if status != gl::FRAMEBUFFER_COMPLETE {
+                return Err(status); //dropping it frees the texture again
+            }
+
+            return Ok(framebuffer);
+        }
+    }
+
+    pub fn bind(&self) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.fbo);
+        }
+    }
+
+    pub const fn size(&self) -> (u32, u32) {
+        (self.width as u32, self.height as u32)
+    }
+
+    //RGBA bytes, bottom row first like everything else GL hands back
+    pub fn read_pixels(&self) -> Vec<u8> {
+        let mut pixels = vec![0u8; self.width as usize * self.height as usize * 4];
+
+        self.bind();
+        unsafe {
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::ReadPixels(0, 0, self.width, self.height, gl::RGBA, gl::UNSIGNED_BYTE, pixels.as_mut_ptr() as *mut _);
+        }
+        unbind_framebuffer();
+
+        return pixels;
+    }
+}
+
+impl Drop for Framebuffer {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteFramebuffers(1, &self.fbo);
+            gl::DeleteTextures(1, &self.texture);
+        }
+    }
+}
+
+//back to drawing into the window
+pub fn unbind_framebuffer() {
+    unsafe {
+        gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+    }
+}
+
 pub fn unbind_vao() {
     unsafe {
         gl::BindVertexArray(0);
     }
 }
 
+pub fn unbind_program() {
+    unsafe {
+        gl::UseProgram(0);
+    }
+}
+
 pub fn unbind_buffer_object(target: BufferTarget) {
     unsafe {
         gl::BindBuffer(target.into_raw(), 0);
@@ -226,8 +524,60 @@ pub fn set_clear_color(color: &Vector4f) {
     }
 }
 
+//standard "over" compositing for straight (non-premultiplied) alpha when enabled
+pub fn set_blending(enabled: bool) {
+    unsafe {
+        if enabled {
+            gl::Enable(gl::BLEND);
+            gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+        } else {
+            gl::Disable(gl::BLEND);
+        }
+    }
+}
+
+pub fn set_viewport(x: i32, y: i32, width: i32, height: i32) {
+    unsafe {
+        gl::Viewport(x, y, width, height);
+    }
+}
+
 pub fn clear_color_buffer() {
     unsafe {
         gl::Clear(gl::COLOR_BUFFER_BIT);
     }
 }
+
+//drains the GL error queue
+pub fn take_gl_errors() -> Vec<GLenum> {
+    let mut errors = Vec::new();
+
+    loop {
+        let error = unsafe { gl::GetError() };
+        if error == gl::NO_ERROR {
+            return errors;
+        }
+
+        errors.push(error);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renderer_error_display() {
+        //the rest is std's own description of the NulError
+        let nul = CString::new("in\0Color").unwrap_err();
+        assert!(RendererError::from(nul).to_string().starts_with("shader source contains a nul byte: "));
+        assert_eq!(
+            RendererError::ShaderCompile("0:1(1): error: syntax error".to_string()).to_string(),
+            "shader compilation failed:\n0:1(1): error: syntax error"
+        );
+        assert_eq!(
+            RendererError::ProgramLink("error: no main".to_string()).to_string(),
+            "shader program linking failed:\nerror: no main"
+        );
+    }
+}
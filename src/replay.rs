@@ -0,0 +1,513 @@
+use crate::game::{
+    BoardLayout, FruitPlacer, IdleShrink, Point, RandomFruitPlacer, ShuffledFruitPlacer, SnakeDirection, SnakeGameField, StartLengthError, MAX_BOARD_SIZE,
+};
+use std::collections::HashSet;
+use std::fmt;
+
+// Binary layout (little endian, varints are unsigned LEB128, flags are 0 or 1):
+//
+// "SNKR" | version: u8 | seed: u64
+// board: size_x: varint | size_y: varint | spawn x, y: varint | obstacle count: varint
+//        | per obstacle x, y: varint | fixed fruit: flag, then x, y: varint if set
+// rules: wrap_x: flag | wrap_y: flag | shuffled fruit: flag | practice: flag | golden chance: f64
+//        | start length: varint | grace ticks: varint | sub-cell steps: varint
+//        | idle shrink ticks: varint, 0 when off | idle shrink fatal: flag | wrap cooldown: varint
+// event count: varint, then per event: tick delta from the previous event: varint | direction: u8
+//
+// A replay covers one round. Together with the seed, the board and the rules are everything that
+// decides how a round plays out, so playback takes them from the replay instead of the command line.
+
+const MAGIC: &[u8; 4] = b"SNKR";
+const VERSION: u8 = 2;
+
+pub struct Replay {
+    pub seed: u64,
+    pub board: BoardLayout,
+    pub rules: ReplayRules,
+    pub events: Vec<ReplayEvent>, //ordered by tick
+}
+
+//the settings of a round that change how it plays out, besides the board
+#[derive(Clone, PartialEq, Debug)]
+pub struct ReplayRules {
+    pub wrap_x: bool,
+    pub wrap_y: bool,
+    pub shuffled_fruit: bool,
+    pub practice: bool,
+    pub golden_chance: f64,
+    pub start_length: usize,
+    pub grace_ticks: u64,
+    pub subcell_steps: u32,
+    pub idle_shrink: Option<IdleShrink>,
+    pub wrap_cooldown: u64,
+}
+
+pub struct ReplayEvent {
+    pub tick: u64,
+    pub direction: SnakeDirection,
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    BadHeader,
+    UnsupportedVersion(u8),
+    Truncated,
+    InvalidDirection(u8),
+    InvalidBoardSize,
+    InvalidBoard, //a spawn, obstacle or fruit off the board
+    InvalidRules,
+    Corrupt,
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::BadHeader => write!(f, "not a snake-rs replay"),
+            ReplayError::UnsupportedVersion(version) => write!(f, "unsupported replay version {}", version),
+            ReplayError::Truncated => write!(f, "replay is truncated"),
+            ReplayError::InvalidDirection(byte) => write!(f, "invalid direction byte {}", byte),
+            ReplayError::InvalidBoardSize => write!(f, "invalid board size"),
+            ReplayError::InvalidBoard => write!(f, "board cell out of bounds"),
+            ReplayError::InvalidRules => write!(f, "invalid rule settings"),
+            ReplayError::Corrupt => write!(f, "replay is corrupt"),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+impl ReplayRules {
+    //the placer a field playing by these rules is created with
+    pub fn fruit_placer(&self) -> Box<dyn FruitPlacer> {
+        if self.shuffled_fruit {
+            Box::new(ShuffledFruitPlacer::new())
+        } else {
+            Box::new(RandomFruitPlacer)
+        }
+    }
+
+    //sets a freshly created field up to play by these rules
+    pub fn apply(&self, field: &mut SnakeGameField) -> Result<(), StartLengthError> {
+        field.wrap_x = self.wrap_x;
+        field.wrap_y = self.wrap_y;
+        field.practice = self.practice;
+        field.golden_chance = self.golden_chance;
+        field.grace_ticks = self.grace_ticks;
+        field.subcell_steps = self.subcell_steps;
+        field.idle_shrink = self.idle_shrink;
+        field.wrap_cooldown = self.wrap_cooldown;
+
+        return field.set_start_length(self.start_length);
+    }
+}
+
+impl Replay {
+    pub fn new(seed: u64, board: BoardLayout, rules: ReplayRules) -> Replay {
+        Replay {
+            seed,
+            board,
+            rules,
+            events: Vec::new(),
+        }
+    }
+
+    // Notes the direction the snake moves in on `tick`, called once per tick in order. Only turns
+    // are kept: the snake starts out heading right and keeps going until the next event.
+    pub fn record(&mut self, tick: u64, direction: SnakeDirection) {
+        let current = self.events.last().map_or(SnakeDirection::Right, |event| event.direction);
+
+        if direction != current {
+            self.events.push(ReplayEvent { tick, direction });
+        }
+    }
+
+    //the turn recorded for `tick`, if the snake turned then
+    pub fn direction_at(&self, tick: u64) -> Option<SnakeDirection> {
+        let index = self.events.binary_search_by_key(&tick, |event| event.tick).ok()?;
+        return Some(self.events[index].direction);
+    }
+}
+
+const fn direction_into_byte(direction: SnakeDirection) -> u8 {
+    match direction {
+        SnakeDirection::Up => 0,
+        SnakeDirection::Right => 1,
+        SnakeDirection::Down => 2,
+        SnakeDirection::Left => 3,
+    }
+}
+
+fn direction_from_byte(byte: u8) -> Result<SnakeDirection, ReplayError> {
+    match byte {
+        0 => Ok(SnakeDirection::Up),
+        1 => Ok(SnakeDirection::Right),
+        2 => Ok(SnakeDirection::Down),
+        3 => Ok(SnakeDirection::Left),
+        _ => Err(ReplayError::InvalidDirection(byte)),
+    }
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let low_bits = (value & 0x7F) as u8;
+        value >>= 7;
+
+        if value == 0 {
+            bytes.push(low_bits);
+            return;
+        }
+
+        bytes.push(low_bits | 0x80);
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> Result<&'a [u8], ReplayError> {
+        if self.bytes.len() < count {
+            return Err(ReplayError::Truncated);
+        }
+
+        let (taken, rest) = self.bytes.split_at(count);
+        self.bytes = rest;
+        return Ok(taken);
+    }
+
+    fn byte(&mut self) -> Result<u8, ReplayError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn varint(&mut self) -> Result<u64, ReplayError> {
+        let mut value = 0u64;
+
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            value |= ((byte & 0x7F) as u64) << shift;
+
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+
+        Err(ReplayError::Corrupt) //more continuation bytes than a u64 can hold
+    }
+
+    fn flag(&mut self) -> Result<bool, ReplayError> {
+        match self.byte()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(ReplayError::Corrupt),
+        }
+    }
+
+    fn f64(&mut self) -> Result<f64, ReplayError> {
+        let mut value = [0u8; 8];
+        value.copy_from_slice(self.take(8)?);
+        return Ok(f64::from_le_bytes(value));
+    }
+
+    //a cell that has to be on a size_x by size_y board
+    fn point(&mut self, size_x: i32, size_y: i32) -> Result<Point, ReplayError> {
+        let (x, y) = (self.varint()?, self.varint()?);
+        if x >= size_x as u64 || y >= size_y as u64 {
+            return Err(ReplayError::InvalidBoard);
+        }
+
+        return Ok(Point::new(x as i32, y as i32));
+    }
+
+    fn board(&mut self) -> Result<BoardLayout, ReplayError> {
+        let board_size = |value: u64| {
+            if value > 0 && value <= MAX_BOARD_SIZE as u64 {
+                Ok(value as i32)
+            } else {
+                Err(ReplayError::InvalidBoardSize)
+            }
+        };
+        let size_x = board_size(self.varint()?)?;
+        let size_y = board_size(self.varint()?)?;
+        let spawn = self.point(size_x, size_y)?;
+
+        let obstacle_count = self.varint()?;
+        let mut obstacles = HashSet::new();
+        for _ in 0..obstacle_count {
+            obstacles.insert(self.point(size_x, size_y)?);
+        }
+
+        let fruit = if self.flag()? { Some(self.point(size_x, size_y)?) } else { None };
+
+        return Ok(BoardLayout {
+            size_x,
+            size_y,
+            spawn,
+            obstacles,
+            fruit,
+        });
+    }
+
+    fn rules(&mut self) -> Result<ReplayRules, ReplayError> {
+        let wrap_x = self.flag()?;
+        let wrap_y = self.flag()?;
+        let shuffled_fruit = self.flag()?;
+        let practice = self.flag()?;
+
+        let golden_chance = self.f64()?;
+        if !(0.0..=1.0).contains(&golden_chance) {
+            return Err(ReplayError::InvalidRules);
+        }
+
+        let start_length = self.varint()?;
+        let grace_ticks = self.varint()?;
+        let subcell_steps = self.varint()?;
+        if start_length == 0 || subcell_steps == 0 || subcell_steps > u32::MAX as u64 {
+            return Err(ReplayError::InvalidRules);
+        }
+
+        let (idle_shrink_ticks, fatal) = (self.varint()?, self.flag()?);
+        let idle_shrink = if idle_shrink_ticks > 0 {
+            Some(IdleShrink {
+                ticks: idle_shrink_ticks,
+                fatal,
+            })
+        } else {
+            None
+        };
+
+        return Ok(ReplayRules {
+            wrap_x,
+            wrap_y,
+            shuffled_fruit,
+            practice,
+            golden_chance,
+            start_length: start_length as usize,
+            grace_ticks,
+            subcell_steps: subcell_steps as u32,
+            idle_shrink,
+            wrap_cooldown: self.varint()?,
+        });
+    }
+}
+
+fn write_point(bytes: &mut Vec<u8>, point: Point) {
+    write_varint(bytes, point.x as u64);
+    write_varint(bytes, point.y as u64);
+}
+
+pub fn encode(replay: &Replay) -> Vec<u8> {
+    let board = &replay.board;
+    let rules = &replay.rules;
+    let mut bytes = Vec::with_capacity(MAGIC.len() + 1 + 8 + board.obstacles.len() * 2 + replay.events.len() * 2);
+
+    bytes.extend_from_slice(MAGIC);
+    bytes.push(VERSION);
+    bytes.extend_from_slice(&replay.seed.to_le_bytes());
+
+    write_varint(&mut bytes, board.size_x as u64);
+    write_varint(&mut bytes, board.size_y as u64);
+    write_point(&mut bytes, board.spawn);
+    //sorted so the same board always encodes the same
+    let mut obstacles = board.obstacles.iter().copied().collect::<Vec<Point>>();
+    obstacles.sort_unstable_by_key(|obstacle| (obstacle.y, obstacle.x));
+    write_varint(&mut bytes, obstacles.len() as u64);
+    for obstacle in obstacles {
+        write_point(&mut bytes, obstacle);
+    }
+    bytes.push(board.fruit.is_some() as u8);
+    if let Some(fruit) = board.fruit {
+        write_point(&mut bytes, fruit);
+    }
+
+    bytes.push(rules.wrap_x as u8);
+    bytes.push(rules.wrap_y as u8);
+    bytes.push(rules.shuffled_fruit as u8);
+    bytes.push(rules.practice as u8);
+    bytes.extend_from_slice(&rules.golden_chance.to_le_bytes());
+    write_varint(&mut bytes, rules.start_length as u64);
+    write_varint(&mut bytes, rules.grace_ticks);
+    write_varint(&mut bytes, rules.subcell_steps as u64);
+    write_varint(&mut bytes, rules.idle_shrink.map_or(0, |idle_shrink| idle_shrink.ticks));
+    bytes.push(matches!(rules.idle_shrink, Some(idle_shrink) if idle_shrink.fatal) as u8);
+    write_varint(&mut bytes, rules.wrap_cooldown);
+
+    write_varint(&mut bytes, replay.events.len() as u64);
+
+    let mut last_tick = 0;
+    for event in &replay.events {
+        write_varint(&mut bytes, event.tick - last_tick);
+        bytes.push(direction_into_byte(event.direction));
+        last_tick = event.tick;
+    }
+
+    return bytes;
+}
+
+pub fn decode(bytes: &[u8]) -> Result<Replay, ReplayError> {
+    let mut reader = Reader { bytes };
+
+    if reader.take(MAGIC.len()).map_err(|_| ReplayError::BadHeader)? != MAGIC {
+        return Err(ReplayError::BadHeader);
+    }
+
+    let version = reader.byte()?;
+    if version != VERSION {
+        return Err(ReplayError::UnsupportedVersion(version));
+    }
+
+    let mut seed = [0u8; 8];
+    seed.copy_from_slice(reader.take(8)?);
+
+    let board = reader.board()?;
+    let rules = reader.rules()?;
+
+    let event_count = reader.varint()?;
+    let mut events = Vec::new();
+
+    let mut tick = 0u64;
+    for _ in 0..event_count {
+        tick = tick.checked_add(reader.varint()?).ok_or(ReplayError::Corrupt)?;
+        let direction = direction_from_byte(reader.byte()?)?;
+        events.push(ReplayEvent { tick, direction });
+    }
+
+    return Ok(Replay {
+        seed: u64::from_le_bytes(seed),
+        board,
+        rules,
+        events,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{autopilot_direction, StepOutcome};
+
+    fn sample_replay() -> Replay {
+        let mut board = BoardLayout::empty(12, 7);
+        board.spawn = Point::new(3, 4);
+        board.obstacles.insert(Point::new(0, 0));
+        board.obstacles.insert(Point::new(11, 6));
+        board.fruit = Some(Point::new(8, 2));
+
+        let rules = ReplayRules {
+            wrap_x: true,
+            wrap_y: false,
+            shuffled_fruit: true,
+            practice: false,
+            golden_chance: 0.25,
+            start_length: 3,
+            grace_ticks: 5,
+            subcell_steps: 2,
+            idle_shrink: Some(IdleShrink { ticks: 40, fatal: true }),
+            wrap_cooldown: 300,
+        };
+
+        let mut replay = Replay::new(0xDEAD_BEEF_1234, board, rules);
+        replay.record(0, SnakeDirection::Right);
+        replay.record(3, SnakeDirection::Up);
+        replay.record(200, SnakeDirection::Left);
+        return replay;
+    }
+
+    #[test]
+    fn encode_decode_round_trips() {
+        let replay = sample_replay();
+        let decoded = decode(&encode(&replay)).unwrap();
+
+        assert_eq!(decoded.seed, replay.seed);
+        assert_eq!(decoded.board, replay.board);
+        assert_eq!(decoded.rules, replay.rules);
+
+        let events = |replay: &Replay| replay.events.iter().map(|event| (event.tick, event.direction)).collect::<Vec<_>>();
+        assert_eq!(events(&decoded), [(3, SnakeDirection::Up), (200, SnakeDirection::Left)]);
+    }
+
+    #[test]
+    fn decode_rejects_a_bad_header() {
+        let mut bytes = encode(&sample_replay());
+        bytes[0] = b'X';
+        assert!(matches!(decode(&bytes), Err(ReplayError::BadHeader)));
+        assert!(matches!(decode(b"SN"), Err(ReplayError::BadHeader)));
+
+        let mut bytes = encode(&sample_replay());
+        bytes[MAGIC.len()] = 1;
+        assert!(matches!(decode(&bytes), Err(ReplayError::UnsupportedVersion(1))));
+    }
+
+    #[test]
+    fn decode_rejects_a_truncated_file() {
+        let bytes = encode(&sample_replay());
+
+        for length in MAGIC.len() + 1..bytes.len() {
+            assert!(matches!(decode(&bytes[..length]), Err(ReplayError::Truncated)), "cut at {} bytes", length);
+        }
+    }
+
+    // Plays a round by `rules` from `seed`, turning wherever `turn` says before each move, until it
+    // ends or `ticks` run out. A recording gets the direction of every move, like the game does.
+    fn play(
+        seed: u64,
+        board: &BoardLayout,
+        rules: &ReplayRules,
+        ticks: u64,
+        mut turn: impl FnMut(&SnakeGameField) -> Option<SnakeDirection>,
+        mut recording: Option<&mut Replay>,
+    ) -> (SnakeGameField, StepOutcome) {
+        let mut field = SnakeGameField::from_layout(board, seed, rules.fruit_placer());
+        rules.apply(&mut field).unwrap();
+
+        let mut outcome = StepOutcome::Continued { ate: false };
+        while field.ticks < ticks && matches!(outcome, StepOutcome::Continued { .. }) {
+            if let Some(direction) = turn(&field) {
+                field.snake.try_change_direction(direction);
+            }
+            if let Some(recording) = recording.as_mut() {
+                recording.record(field.ticks, field.snake.direction);
+            }
+
+            outcome = field.step(None);
+        }
+
+        return (field, outcome);
+    }
+
+    #[test]
+    fn playing_back_a_recorded_round_ends_in_the_same_state() {
+        let mut board = BoardLayout::empty(9, 9);
+        board.obstacles.insert(Point::new(2, 6));
+        let rules = ReplayRules {
+            wrap_x: true,
+            wrap_y: false,
+            shuffled_fruit: false,
+            practice: false,
+            golden_chance: 0.3,
+            start_length: 3,
+            grace_ticks: 0,
+            subcell_steps: 1,
+            idle_shrink: None,
+            wrap_cooldown: 4,
+        };
+
+        const TICKS: u64 = 200;
+        //the autopilot stands in for a player, its turns depend on where the fruit shows up
+        let driver = |field: &SnakeGameField| Some(autopilot_direction(field));
+
+        let mut recording = Replay::new(7, board.clone(), rules.clone());
+        let (recorded, recorded_outcome) = play(7, &board, &rules, TICKS, driver, Some(&mut recording));
+
+        let replay = decode(&encode(&recording)).unwrap();
+        let (played, played_outcome) = play(replay.seed, &replay.board, &replay.rules, TICKS, |field| replay.direction_at(field.ticks), None);
+
+        assert_eq!(played_outcome, recorded_outcome);
+        assert_eq!(played.ticks, recorded.ticks);
+        assert_eq!(played.snake.head, recorded.snake.head);
+        assert_eq!(played.snake.tail, recorded.snake.tail);
+        let fruit = |field: &SnakeGameField| field.fruit.as_ref().map(|fruit| (fruit.0, fruit.1));
+        assert_eq!(fruit(&played), fruit(&recorded));
+        assert_eq!(played.score(), recorded.score());
+        assert!(recorded.score() >= 5, "only {} fruits eaten", recorded.score());
+    }
+}
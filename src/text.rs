@@ -0,0 +1,207 @@
+use crate::create_shader_program;
+use crate::math::{ortho2d, Matrix4f, Vector4f};
+use crate::renderer::*;
+use gl::types::*;
+use std::mem;
+use std::os::raw::c_void;
+
+// A tiny bitmap font for on-screen text. Every glyph is 5x7 pixels, one row per byte with the
+// leftmost pixel in bit 4. Lowercase letters are drawn as uppercase, characters without a glyph as '?'.
+
+const TEXT_VERTEX_SHADER_SRC: &str = include_str!("../assets/vertex_text.glsl");
+const TEXT_FRAGMENT_SHADER_SRC: &str = include_str!("../assets/fragment_text.glsl");
+
+const GLYPH_WIDTH: usize = 5;
+const GLYPH_HEIGHT: usize = 7;
+const GLYPH_ADVANCE: usize = GLYPH_WIDTH + 1; //one pixel between letters, also keeps glyphs apart in the atlas
+
+#[rustfmt::skip]
+const GLYPHS: [(char, [u8; GLYPH_HEIGHT]); 44] = [
+    ('A', [0b01110, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001]),
+    ('B', [0b11110, 0b10001, 0b10001, 0b11110, 0b10001, 0b10001, 0b11110]),
+    ('C', [0b01110, 0b10001, 0b10000, 0b10000, 0b10000, 0b10001, 0b01110]),
+    ('D', [0b11110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b11110]),
+    ('E', [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b11111]),
+    ('F', [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b10000]),
+    ('G', [0b01110, 0b10001, 0b10000, 0b10111, 0b10001, 0b10001, 0b01111]),
+    ('H', [0b10001, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001]),
+    ('I', [0b01110, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110]),
+    ('J', [0b00111, 0b00010, 0b00010, 0b00010, 0b00010, 0b10010, 0b01100]),
+    ('K', [0b10001, 0b10010, 0b10100, 0b11000, 0b10100, 0b10010, 0b10001]),
+    ('L', [0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b11111]),
+    ('M', [0b10001, 0b11011, 0b10101, 0b10101, 0b10001, 0b10001, 0b10001]),
+    ('N', [0b10001, 0b10001, 0b11001, 0b10101, 0b10011, 0b10001, 0b10001]),
+    ('O', [0b01110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110]),
+    ('P', [0b11110, 0b10001, 0b10001, 0b11110, 0b10000, 0b10000, 0b10000]),
+    ('Q', [0b01110, 0b10001, 0b10001, 0b10001, 0b10101, 0b10010, 0b01101]),
+    ('R', [0b11110, 0b10001, 0b10001, 0b11110, 0b10100, 0b10010, 0b10001]),
+    ('S', [0b01111, 0b10000, 0b10000, 0b01110, 0b00001, 0b00001, 0b11110]),
+    ('T', [0b11111, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100]),
+    ('U', [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110]),
+    ('V', [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01010, 0b00100]),
+    ('W', [0b10001, 0b10001, 0b10001, 0b10101, 0b10101, 0b10101, 0b01010]),
+    ('X', [0b10001, 0b10001, 0b01010, 0b00100, 0b01010, 0b10001, 0b10001]),
+    ('Y', [0b10001, 0b10001, 0b01010, 0b00100, 0b00100, 0b00100, 0b00100]),
+    ('Z', [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b11111]),
+    ('0', [0b01110, 0b10001, 0b10011, 0b10101, 0b11001, 0b10001, 0b01110]),
+    ('1', [0b00100, 0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110]),
+    ('2', [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b01000, 0b11111]),
+    ('3', [0b11111, 0b00010, 0b00100, 0b00010, 0b00001, 0b10001, 0b01110]),
+    ('4', [0b00010, 0b00110, 0b01010, 0b10010, 0b11111, 0b00010, 0b00010]),
+    ('5', [0b11111, 0b10000, 0b11110, 0b00001, 0b00001, 0b10001, 0b01110]),
+    ('6', [0b00110, 0b01000, 0b10000, 0b11110, 0b10001, 0b10001, 0b01110]),
+    ('7', [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b01000, 0b01000]),
+    ('8', [0b01110, 0b10001, 0b10001, 0b01110, 0b10001, 0b10001, 0b01110]),
+    ('9', [0b01110, 0b10001, 0b10001, 0b01111, 0b00001, 0b00010, 0b01100]),
+    (' ', [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000]),
+    ('!', [0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00000, 0b00100]),
+    ('?', [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b00000, 0b00100]),
+    (':', [0b00000, 0b01100, 0b01100, 0b00000, 0b01100, 0b01100, 0b00000]),
+    ('-', [0b00000, 0b00000, 0b00000, 0b11111, 0b00000, 0b00000, 0b00000]),
+    ('.', [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b01100]),
+    (',', [0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b00100, 0b01000]),
+    ('/', [0b00001, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b10000]),
+];
+
+const VERTEX_FLOATS: usize = 2 + 2; //position, texture coordinate
+
+//index into GLYPHS of the glyph drawn for `c`
+fn glyph_index(c: char) -> usize {
+    let c = c.to_ascii_uppercase();
+    let find = |c: char| GLYPHS.iter().position(|(glyph, _)| *glyph == c);
+    find(c).or_else(|| find('?')).unwrap()
+}
+
+//the glyphs side by side in one row, white with the glyph pixels opaque
+fn build_atlas() -> (u32, u32, Vec<u8>) {
+    let width = GLYPHS.len() * GLYPH_ADVANCE;
+    let mut pixels = vec![0u8; width * GLYPH_HEIGHT * 4];
+
+    for (index, (_, rows)) in GLYPHS.iter().enumerate() {
+        for (y, row) in rows.iter().enumerate() {
+            for x in 0..GLYPH_WIDTH {
+                let lit = row & (1 << (GLYPH_WIDTH - 1 - x)) != 0;
+                let pixel = (y * width + index * GLYPH_ADVANCE + x) * 4;
+                pixels[pixel..pixel + 4].copy_from_slice(&[255, 255, 255, if lit { 255 } else { 0 }]);
+            }
+        }
+    }
+
+    return (width as u32, GLYPH_HEIGHT as u32, pixels);
+}
+
+// Draws strings in window coordinates: (0, 0) is the top left corner of the window and one unit is
+// one pixel of a window at its initial size. Text is drawn straight away, blended over whatever is
+// there, and leaves its own program in use.
+pub struct TextRenderer {
+    vao: VertexArrayObject, //goes before the vertex buffer it references
+    vbo: BufferObject,
+    texture: Texture,
+    program: ShaderProgram,
+    projection_uniform: UniformLocation,
+    color_uniform: UniformLocation,
+    glyphs_uniform: UniformLocation,
+    projection: Matrix4f,
+    vertices: Vec<f32>, //reused between calls
+}
+
+impl TextRenderer {
+    pub fn setup(window_width: u32, window_height: u32) -> Result<TextRenderer, RendererError> {
+        let program = create_shader_program(TEXT_VERTEX_SHADER_SRC, TEXT_FRAGMENT_SHADER_SRC)?;
+        let projection_uniform = UniformLocation::get(&program, "projection");
+        let color_uniform = UniformLocation::get(&program, "inColor");
+        let glyphs_uniform = UniformLocation::get(&program, "glyphs");
+
+        let (atlas_width, atlas_height, atlas) = build_atlas();
+        let texture = Texture::from_rgba(atlas_width, atlas_height, &atlas);
+
+        let vbo = BufferObject::gen();
+        let vao = VertexArrayObject::gen();
+        vao.bind();
+        vbo.bind(BufferTarget::ArrayBuffer);
+        vao.float_attribute(0, 2, VERTEX_FLOATS, 0); //position
+        vao.float_attribute(1, 2, VERTEX_FLOATS, 2); //texture coordinate
+
+        unbind_vao();
+        unbind_buffer_object(BufferTarget::ArrayBuffer);
+
+        return Ok(TextRenderer {
+            vao,
+            vbo,
+            texture,
+            program,
+            projection_uniform,
+            color_uniform,
+            glyphs_uniform,
+            projection: ortho2d(0.0, window_width as f32, window_height as f32, 0.0),
+            vertices: Vec::new(),
+        });
+    }
+
+    //width of `text` drawn at `scale`, for lining it up
+    pub fn text_width(text: &str, scale: f32) -> f32 {
+        let count = text.chars().count();
+        if count == 0 {
+            return 0.0;
+        }
+
+        return (count * GLYPH_ADVANCE - 1) as f32 * scale;
+    }
+
+    pub fn text_height(scale: f32) -> f32 {
+        GLYPH_HEIGHT as f32 * scale
+    }
+
+    //`scale` is the size of one font pixel, (x, y) is the top left corner of the first letter
+    pub fn draw_text(&mut self, text: &str, x: f32, y: f32, scale: f32, color: &Vector4f) {
+        self.vertices.clear();
+
+        let atlas_width = (GLYPHS.len() * GLYPH_ADVANCE) as f32;
+        let (glyph_width, glyph_height) = (GLYPH_WIDTH as f32 * scale, GLYPH_HEIGHT as f32 * scale);
+
+        for (position, c) in text.chars().enumerate() {
+            let left = x + (position * GLYPH_ADVANCE) as f32 * scale;
+            let (right, bottom) = (left + glyph_width, y + glyph_height);
+
+            let u_left = (glyph_index(c) * GLYPH_ADVANCE) as f32 / atlas_width;
+            let u_right = u_left + GLYPH_WIDTH as f32 / atlas_width;
+
+            //two triangles per glyph, v = 0 is the top row of the atlas
+            #[rustfmt::skip]
+            self.vertices.extend([
+                left, y, u_left, 0.0,
+                right, y, u_right, 0.0,
+                right, bottom, u_right, 1.0,
+                left, y, u_left, 0.0,
+                right, bottom, u_right, 1.0,
+                left, bottom, u_left, 1.0,
+            ]);
+        }
+
+        if self.vertices.is_empty() {
+            return;
+        }
+
+        self.vbo.bind(BufferTarget::ArrayBuffer);
+
+        unsafe {
+            gl::BufferData(
+                BufferTarget::ArrayBuffer.into_raw(),
+                (self.vertices.len() * mem::size_of::<GLfloat>()) as GLsizeiptr,
+                self.vertices.as_ptr() as *const c_void,
+                GlDrawType::Stream.into_raw(),
+            );
+        }
+
+        unbind_buffer_object(BufferTarget::ArrayBuffer);
+
+        self.texture.bind(0);
+        self.program.use_program();
+        self.program.set_uniform_mat4(&self.projection_uniform, &self.projection);
+        self.program.set_uniform_vec4(&self.color_uniform, color);
+        self.program.set_uniform_sampler(&self.glyphs_uniform, 0);
+
+        set_blending(true);
+        self.vao.draw_arrays((self.vertices.len() / VERTEX_FLOATS) as GLsizei);
+    }
+}
@@ -0,0 +1,218 @@
+use crate::math::Vector4f;
+use std::path::Path;
+use std::{fmt, fs, io};
+
+// Theme files are plain text, one "key = #rrggbb" (or #rrggbbaa) per line. Blank lines and lines
+// starting with '#' are skipped, keys that aren't listed keep the default color:
+//
+// # warm
+// background = #1e1a16
+// fruit = #ff8040
+
+#[derive(Clone, Debug)]
+pub struct Theme {
+    pub background: Vector4f, //clear color behind the board
+    pub square: Vector4f,     //the board itself
+    pub snake: Vector4f,      //first stop of the body gradient
+    pub snake_head: Vector4f,
+    pub fruit: Vector4f,
+    pub checker_dark: Vector4f, //the two cell shades with --checker, dark where x + y is even
+    pub checker_light: Vector4f,
+}
+
+#[derive(Debug)]
+pub enum ThemeError {
+    Io(io::Error),
+    MissingSeparator { line: usize },
+    UnknownKey { line: usize, key: String },
+    InvalidColor { line: usize, value: String },
+}
+
+impl fmt::Display for ThemeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ThemeError::Io(error) => write!(f, "{}", error),
+            ThemeError::MissingSeparator { line } => write!(f, "line {}: expected 'key = #rrggbb'", line),
+            ThemeError::UnknownKey { line, key } => write!(f, "line {}: unknown color '{}'", line, key),
+            ThemeError::InvalidColor { line, value } => write!(f, "line {}: '{}' is not a #rrggbb or #rrggbbaa color", line, value),
+        }
+    }
+}
+
+impl std::error::Error for ThemeError {}
+
+impl From<io::Error> for ThemeError {
+    fn from(error: io::Error) -> ThemeError {
+        ThemeError::Io(error)
+    }
+}
+
+pub const BUILTIN_THEMES: [&str; 3] = ["classic", "light", "mono"];
+
+//theme files that ship with the game, embedded like the other assets so their names work from anywhere
+pub const SHIPPED_THEMES: [(&str, &str); 1] = [("warm", include_str!("../assets/themes/warm.theme"))];
+
+//every name --theme takes besides a path
+pub fn theme_names() -> Vec<&'static str> {
+    BUILTIN_THEMES.iter().copied().chain(SHIPPED_THEMES.iter().map(|(name, _)| *name)).collect()
+}
+
+impl Default for Theme {
+    fn default() -> Theme {
+        Theme {
+            background: Vector4f::new(0.0, 0.0, 0.0, 1.0),
+            square: Vector4f::new(0.26, 0.28, 0.32, 1.0),
+            snake: Vector4f::new(1.0, 1.0, 1.0, 1.0),
+            snake_head: Vector4f::new(0.75, 1.0, 0.7, 1.0), //pale green, shows which end leads
+            fruit: Vector4f::new(0.984, 0.11, 0.369, 1.0),
+            checker_dark: Vector4f::new(0.29, 0.31, 0.35, 1.0),
+            checker_light: Vector4f::new(0.33, 0.35, 0.39, 1.0),
+        }
+    }
+}
+
+impl Theme {
+    //one of BUILTIN_THEMES, "classic" is the default
+    pub fn builtin(name: &str) -> Option<Theme> {
+        match name {
+            "classic" => Some(Theme::default()),
+            "light" => Some(Theme {
+                background: Vector4f::new(0.93, 0.92, 0.89, 1.0),
+                square: Vector4f::new(0.82, 0.8, 0.75, 1.0),
+                snake: Vector4f::new(0.2, 0.24, 0.3, 1.0),
+                snake_head: Vector4f::new(0.13, 0.45, 0.25, 1.0),
+                fruit: Vector4f::new(0.85, 0.2, 0.15, 1.0),
+                checker_dark: Vector4f::new(0.79, 0.77, 0.72, 1.0),
+                checker_light: Vector4f::new(0.86, 0.84, 0.79, 1.0),
+            }),
+            "mono" => Some(Theme {
+                background: Vector4f::new(0.0, 0.0, 0.0, 1.0),
+                square: Vector4f::new(0.2, 0.2, 0.2, 1.0),
+                snake: Vector4f::new(0.85, 0.85, 0.85, 1.0),
+                snake_head: Vector4f::new(1.0, 1.0, 1.0, 1.0),
+                fruit: Vector4f::new(0.55, 0.55, 0.55, 1.0),
+                checker_dark: Vector4f::new(0.17, 0.17, 0.17, 1.0),
+                checker_light: Vector4f::new(0.23, 0.23, 0.23, 1.0),
+            }),
+            _ => None,
+        }
+    }
+
+    //what --theme names: a built-in theme, then one of SHIPPED_THEMES, and only then a path to a theme file
+    pub fn resolve(name: &str) -> Result<Theme, ThemeError> {
+        if let Some(theme) = Theme::builtin(name) {
+            return Ok(theme);
+        }
+
+        if let Some((_, source)) = SHIPPED_THEMES.iter().find(|(shipped, _)| *shipped == name) {
+            return Theme::parse(source);
+        }
+
+        return Theme::load(Path::new(name));
+    }
+
+    pub fn load(path: &Path) -> Result<Theme, ThemeError> {
+        let source = fs::read_to_string(path)?;
+        return Theme::parse(&source);
+    }
+
+    pub fn parse(source: &str) -> Result<Theme, ThemeError> {
+        let mut theme = Theme::default();
+
+        for (index, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let line_number = index + 1;
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => return Err(ThemeError::MissingSeparator { line: line_number }),
+            };
+
+            let color = match key {
+                "background" => &mut theme.background,
+                "square" => &mut theme.square,
+                "checker_dark" => &mut theme.checker_dark,
+                "checker_light" => &mut theme.checker_light,
+                "snake" => &mut theme.snake,
+                "snake_head" => &mut theme.snake_head,
+                "fruit" => &mut theme.fruit,
+                _ => {
+                    return Err(ThemeError::UnknownKey {
+                        line: line_number,
+                        key: key.to_string(),
+                    })
+                }
+            };
+
+            *color = parse_hex_color(value).ok_or_else(|| ThemeError::InvalidColor {
+                line: line_number,
+                value: value.to_string(),
+            })?;
+        }
+
+        return Ok(theme);
+    }
+}
+
+//"#rrggbb" or "#rrggbbaa", the '#' is optional
+fn parse_hex_color(value: &str) -> Option<Vector4f> {
+    let digits = value.strip_prefix('#').unwrap_or(value);
+    if !(digits.len() == 6 || digits.len() == 8) || !digits.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let channel = |index: usize| {
+        u8::from_str_radix(&digits[index * 2..index * 2 + 2], 16)
+            .ok()
+            .map(|channel| channel as f32 / 255.0)
+    };
+    let alpha = if digits.len() == 8 { channel(3)? } else { 1.0 };
+
+    return Some(Vector4f::new(channel(0)?, channel(1)?, channel(2)?, alpha));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_overrides_listed_colors_only() {
+        let theme = Theme::parse("# warm\n\nbackground = #ff0000\nfruit=00ff0080\n").unwrap();
+
+        assert_eq!(theme.background, Vector4f::new(1.0, 0.0, 0.0, 1.0));
+        assert_eq!(theme.fruit, Vector4f::new(0.0, 1.0, 0.0, 128.0 / 255.0));
+        assert_eq!(theme.square, Theme::default().square);
+    }
+
+    #[test]
+    fn parse_reports_the_line_of_each_error() {
+        assert!(matches!(Theme::parse("\nbackground #000000"), Err(ThemeError::MissingSeparator { line: 2 })));
+        assert!(matches!(Theme::parse("walls = #000000"), Err(ThemeError::UnknownKey { line: 1, key }) if key == "walls"));
+
+        for value in ["#00000", "#0000000", "#gg0000", "red", ""] {
+            let source = format!("snake = #ffffff\nsnake = {}", value);
+            assert!(
+                matches!(Theme::parse(&source), Err(ThemeError::InvalidColor { line: 2, .. })),
+                "accepted '{}'",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn names_resolve_to_shipped_themes_before_paths() {
+        let warm = Theme::resolve("warm").unwrap();
+        assert_eq!(warm.fruit, parse_hex_color("#ff8040").unwrap());
+        assert_eq!(Theme::resolve("light").unwrap().fruit, Theme::builtin("light").unwrap().fruit);
+
+        //not a name, so it's a path, and there is no such file
+        assert!(matches!(Theme::resolve("no-such-theme"), Err(ThemeError::Io(_))));
+
+        for (name, source) in SHIPPED_THEMES {
+            assert!(Theme::parse(source).is_ok(), "shipped theme '{}' doesn't parse", name);
+        }
+    }
+}